@@ -76,6 +76,12 @@ static ESPEAKNG_INIT: Lazy<ESpeakResult<()>> = Lazy::new(|| {
     }
 });
 
+/// Initializes eSpeak-ng if that did not happen yet.
+/// Fails if the `espeak-ng-data` directory could not be found.
+pub fn initialize() -> ESpeakResult<()> {
+    Lazy::force(&ESPEAKNG_INIT).clone()
+}
+
 pub fn text_to_phonemes(
     text: &str,
     language: &str,
@@ -103,9 +109,7 @@ pub fn _text_to_phonemes(
     remove_lang_switch_flags: bool,
     remove_stress: bool,
 ) -> ESpeakResult<Vec<String>> {
-    if let Err(ref e) = Lazy::force(&ESPEAKNG_INIT) {
-        return Err(e.clone());
-    }
+    initialize()?;
     let set_voice_res = unsafe { espeak_rs_sys::espeak_SetVoiceByName(rust_string_to_c(language)) };
     if set_voice_res != espeak_rs_sys::espeak_ERROR_EE_OK {
         return Err(ESpeakError(format!(
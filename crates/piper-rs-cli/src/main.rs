@@ -7,13 +7,11 @@ cargo run -p piper-rs-cli en_US-hfc_female-medium.onnx.json "Hello from piper-rs
 
 use clap::Parser;
 use console::style;
-use eyre::{bail, Result};
+use eyre::Result;
 use piper_rs::synth::PiperSpeechSynthesizer;
+use piper_rs::{PiperError, PiperModelBuilder};
 use rodio::buffer::SamplesBuffer;
-use std::{
-    path::{Path, PathBuf},
-    time::Instant,
-};
+use std::{path::PathBuf, time::Instant};
 use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
 /// Simple program to greet a person
@@ -35,6 +33,14 @@ struct Args {
     #[arg(short, long)]
     model: Option<String>,
 
+    /// Path to the encoder of a streaming model
+    #[arg(long, requires = "decoder", conflicts_with = "model")]
+    encoder: Option<String>,
+
+    /// Path to the decoder of a streaming model
+    #[arg(long, requires = "encoder", conflicts_with = "model")]
+    decoder: Option<String>,
+
     /// Path to Model
     #[arg(long)]
     speaker_id: Option<i64>,
@@ -65,11 +71,11 @@ fn main() {
     match run(&args) {
         Ok(_) => {}
         Err(e) => {
-            if e.to_string().contains("Failed to initialize eSpeak-ng") {
-                if args.verbose {
-                    eprintln!("{:?}", e);
-                }
-            } else {
+            let espeak_init_failed = matches!(
+                e.downcast_ref::<PiperError>(),
+                Some(PiperError::ESpeakInit(_))
+            );
+            if !espeak_init_failed || args.verbose {
                 eprintln!("{:?}", e);
             }
             show_error_hint();
@@ -78,15 +84,14 @@ fn main() {
 }
 
 fn run(args: &Args) -> Result<()> {
-    let model = args
-        .model
-        .clone()
-        .map(|m| PathBuf::from(m))
-        .unwrap_or_else(|| PathBuf::from(&args.config.replace(".onnx.json", ".onnx")));
-    if !model.exists() {
-        bail!("Model not found at {}", model.display())
+    let mut builder = PiperModelBuilder::new(&args.config);
+    if let Some(model) = &args.model {
+        builder = builder.onnx_path(model);
+    }
+    if let (Some(encoder), Some(decoder)) = (&args.encoder, &args.decoder) {
+        builder = builder.encoder_path(encoder).decoder_path(decoder);
     }
-    let model = piper_rs::from_config_path(Path::new(&args.config)).unwrap();
+    let model = builder.build()?;
     if let Some(sid) = args.speaker_id {
         model.set_speaker(sid);
     }
@@ -19,14 +19,14 @@ static HANN_WINDOW_LOOKUP_TABLE: Lazy<HashMap<usize, Vec<f32>>> = Lazy::new(|| {
 /// Compute a Hann window of the given length.
 ///
 /// This function takes an integer `window_length` representing the desired length of the Hann window,
-/// and returns an `Vec<f32>` containing the Hann window values. If the `window_length` is less
-/// than or equal to 1, or greater than the allowed maximum, an error is returned. If the `window_length`
+/// and returns an `Vec<f32>` containing the Hann window values. A `window_length` of 0 gives an empty
+/// window and a `window_length` of 1 leaves the single sample untouched. If the `window_length`
 /// is in the precomputed lookup table, the precomputed values are returned. Otherwise, the Hann window
 /// values are computed using the formula `w(n) = 0.5 - 0.5 * cos(2π * n / (N - 1))`, where `n` is the
 /// index of the current sample and `N` is the length of the window.
 pub fn get_hann_window(window_length: usize) -> Vec<f32> {
-    if window_length == 0 {
-        panic!("Invalid window length: 0")
+    if window_length <= 1 {
+        return vec![1.0; window_length];
     }
     // Check if the window length is in the lookup table.
     if let Some(hann_window) = HANN_WINDOW_LOOKUP_TABLE.get(&window_length) {
@@ -3,5 +3,5 @@ mod samples;
 pub mod synth;
 mod wave_writer;
 
-pub use samples::{Audio, AudioInfo, AudioSamples};
+pub use samples::{Audio, AudioChunk, AudioInfo, AudioSamples, PhonemeAlignment, SynthesisTimings};
 pub use wave_writer::{write_wave_samples_to_file, WaveWriterError};
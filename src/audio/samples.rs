@@ -1,5 +1,6 @@
 use super::hanning_window;
 use std::path::Path;
+use std::time::Duration;
 
 const PI: f32 = std::f32::consts::PI;
 const I16MIN_F32: f32 = i16::MIN as f32;
@@ -50,21 +51,9 @@ impl AudioSamples {
         if self.is_empty() {
             return Default::default();
         }
-        let min_audio_value = self
-            .0
-            .iter()
-            .min_by(|x, y| x.partial_cmp(y).unwrap())
-            .unwrap();
-        let max_audio_value = self
-            .0
-            .iter()
-            .max_by(|x, y| x.partial_cmp(y).unwrap())
-            .unwrap();
-        let abs_max = max_audio_value
-            .abs()
-            .max(min_audio_value.abs())
-            .max(f32::EPSILON);
+        let abs_max = self.abs_max().max(f32::EPSILON);
         let audio_scale = MAX_WAV_VALUE_I16 / abs_max;
+        // NaN samples become silence
         Vec::from_iter(
             self.0
                 .iter()
@@ -81,15 +70,15 @@ impl AudioSamples {
         if self.is_empty() {
             return;
         }
-        let self_max = self
-            .0
-            .iter()
-            .max_by(|x, y| x.partial_cmp(y).unwrap())
-            .unwrap()
-            .abs();
+        // `f32::max` ignores NaN samples
+        let self_max = self.0.iter().copied().fold(f32::MIN, f32::max).abs();
         let factor = self_max.max(max_value) / max_value.abs();
         self.0.iter_mut().for_each(|f| *f /= factor);
     }
+    /// Largest absolute sample value, ignoring NaN samples
+    fn abs_max(&self) -> f32 {
+        self.0.iter().fold(0.0f32, |max, f| max.max(f.abs()))
+    }
     pub fn apply_hanning_window(&mut self) {
         let samples = self.0.as_mut_slice();
         let h_win = hanning_window::get_hann_window(samples.len());
@@ -142,6 +131,9 @@ impl AudioSamples {
     pub fn crossfade(&mut self, fade_samples: usize) {
         let length = self.len();
         let fade_samples = fade_samples.min(length / 2);
+        if fade_samples < 2 {
+            return;
+        }
         let attenuation_factor = (fade_samples - 1) as f32;
         let fade = (0..fade_samples)
             .map(|i| i as f32 / attenuation_factor)
@@ -203,12 +195,45 @@ impl IntoIterator for AudioSamples {
     }
 }
 
+/// Time span of one model input phoneme id in the synthesized audio,
+/// relative to the start of the sentence
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhonemeAlignment {
+    pub phoneme_id: i64,
+    /// The phoneme mapped to this id, if the id belongs to a single phoneme
+    pub phoneme: Option<char>,
+    pub start_ms: f32,
+    pub end_ms: f32,
+}
+
+/// Time spent in each stage of producing a sentence or a streamed chunk.
+///
+/// Stages that did not run for this piece of audio, or whose cost was already
+/// reported with an earlier piece of the same request, are `None`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SynthesisTimings {
+    /// Phonemizing the whole text, reported with the first audio of a request
+    pub phonemization: Option<Duration>,
+    pub phoneme_ids: Option<Duration>,
+    pub encoder: Option<Duration>,
+    /// Decoder runs of this audio. Non-streaming models run encoder and decoder
+    /// as a single session, which is reported here.
+    pub decoder: Option<Duration>,
+    /// Applying rate, volume and pitch with sonic
+    pub post_processing: Option<Duration>,
+    /// From the start of the request until its first audio was ready
+    pub time_to_first_chunk: Option<Duration>,
+}
+
 #[derive(Debug, Clone)]
 #[must_use]
 pub struct Audio {
     pub samples: AudioSamples,
     pub info: AudioInfo,
     pub inference_ms: Option<f32>,
+    /// Per-phoneme timing, available when the model exports phoneme durations
+    pub alignment: Option<Vec<PhonemeAlignment>>,
+    pub timings: SynthesisTimings,
 }
 
 impl Audio {
@@ -216,6 +241,8 @@ impl Audio {
         Self {
             samples,
             inference_ms,
+            alignment: None,
+            timings: SynthesisTimings::default(),
             info: AudioInfo {
                 sample_rate,
                 num_channels: 1,
@@ -268,6 +295,39 @@ impl Audio {
     }
 }
 
+/// A piece of a sentence produced by streaming synthesis
+#[derive(Debug, Clone, Default)]
+#[must_use]
+pub struct AudioChunk {
+    pub samples: AudioSamples,
+    /// Alignment relative to the start of the sentence. Streaming models attach the whole
+    /// sentence to its first chunk, non-streaming models attach each clause to its chunk.
+    pub alignment: Option<Vec<PhonemeAlignment>>,
+    pub timings: SynthesisTimings,
+}
+
+impl AudioChunk {
+    pub fn into_vec(self) -> Vec<f32> {
+        self.samples.into_vec()
+    }
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+}
+
+impl From<AudioSamples> for AudioChunk {
+    fn from(samples: AudioSamples) -> Self {
+        Self {
+            samples,
+            alignment: None,
+            timings: SynthesisTimings::default(),
+        }
+    }
+}
+
 impl IntoIterator for Audio {
     type Item = f32;
     type IntoIter = std::vec::IntoIter<Self::Item>;
@@ -338,6 +398,22 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_nan_and_tiny_inputs() {
+        let samples = AudioSamples::from(vec![f32::NAN, 0.5, -1.0]);
+        assert_eq!(samples.to_i16_vec(), vec![0, 16383, -32767]);
+        let mut samples = AudioSamples::from(vec![f32::NAN, 2.0]);
+        samples.normalize(1.0);
+        assert_eq!(samples.0[1], 1.0);
+
+        for len in 0..3 {
+            let mut samples = AudioSamples::from(vec![1.0; len]);
+            samples.crossfade(42);
+            samples.apply_hanning_window();
+            assert_eq!(samples.len(), len);
+        }
+    }
+
     #[test]
     fn test_strip_silence() {
         let data = vec![0.0, 0.1, 2.2, 0.0, 0.5, 0.0, 0.7, 0.0];
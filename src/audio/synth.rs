@@ -1,19 +1,28 @@
-use flume::{Receiver, SendError, Sender};
-use once_cell::sync::Lazy;
+use flume::r#async::RecvStream;
+use flume::{Receiver, SendTimeoutError, Sender};
+use futures_core::Stream;
+use once_cell::sync::{Lazy, OnceCell};
 use rayon::prelude::*;
 use rayon::{ThreadPool, ThreadPoolBuilder};
-use std::any::Any;
 use std::collections::HashMap;
 use std::path::Path;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
 
 use crate::audio;
 use crate::core::{
-    Audio, AudioInfo, AudioSamples, AudioStreamIterator, Phonemes, PiperAudioResult, PiperError,
-    PiperModel, PiperResult,
+    Audio, AudioChunk, AudioInfo, AudioSamples, AudioStreamIterator, PhonemeAlignment, Phonemes,
+    PiperAudioResult, PiperError, PiperModel, PiperResult, PiperSynthesisConfig, Speaker,
+    SynthesisTimings, WarmUpTimings,
 };
+use crate::model_info::SessionInfo;
+use crate::phoneme_ids::PhonemeIds;
+use crate::speaker_embedding::SpeakerEmbedding;
+use crate::ssml::{parse_ssml, Prosody, SsmlPiece, SsmlSegment};
 
-#[allow(dead_code)]
 pub fn param_to_percent(value: f32, min: f32, max: f32) -> u8 {
     ((value - min) / (max - min) * 100.0f32).round() as u8
 }
@@ -25,6 +34,14 @@ pub fn percent_to_param(value: u8, min: f32, max: f32) -> f32 {
 const RATE_RANGE: (f32, f32) = (0.5f32, 5.5f32);
 const VOLUME_RANGE: (f32, f32) = (0.0f32, 1.0f32);
 const PITCH_RANGE: (f32, f32) = (0.5f32, 1.5f32);
+/// Loudest SSML prosody volume, the gain of `x-loud`
+const MAX_SSML_VOLUME: f32 = 2.0;
+/// Slowest SSML prosody rate, keeps the length scale finite
+const MIN_SSML_RATE: f32 = 0.1;
+/// Number of chunks a realtime stream buffers ahead of its consumer by default
+pub const DEFAULT_STREAM_CAPACITY: usize = 16;
+/// How often a producer blocked on a full stream checks for cancellation
+const CANCELLATION_POLL_INTERVAL: Duration = Duration::from_millis(20);
 
 pub static SYNTHESIS_THREAD_POOL: Lazy<ThreadPool> = Lazy::new(|| {
     let num_cpus = std::thread::available_parallelism()
@@ -37,9 +54,10 @@ pub static SYNTHESIS_THREAD_POOL: Lazy<ThreadPool> = Lazy::new(|| {
         .unwrap()
 });
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct AudioOutputConfig {
     pub rate: Option<u8>,
+    /// Percent of the voice's volume, above 100 amplifies it and clips the samples to [-1, 1]
     pub volume: Option<u8>,
     pub pitch: Option<u8>,
     pub appended_silence_ms: Option<u32>,
@@ -47,6 +65,10 @@ pub struct AudioOutputConfig {
 
 impl AudioOutputConfig {
     fn apply(&self, mut audio: Audio) -> PiperAudioResult {
+        let timer = Instant::now();
+        if let Some(alignment) = audio.alignment.as_mut() {
+            self.apply_to_alignment(alignment);
+        }
         let mut samples = audio.samples.take();
         if let Some(time_ms) = self.appended_silence_ms {
             let mut silence_samples = self.generate_silence(
@@ -62,8 +84,38 @@ impl AudioOutputConfig {
             audio.info.num_channels,
         )?;
         audio.samples.as_mut_vec().append(samples.as_mut_vec());
+        audio.timings.post_processing = Some(timer.elapsed());
         Ok(audio)
     }
+    /// Scales the pitch and volume of this config by the factors of SSML prosody
+    fn with_prosody(&self, prosody: Prosody) -> Self {
+        let scale = |percent: Option<u8>, factor: f32, (min, max): (f32, f32), ceiling: f32| {
+            if factor == 1.0 {
+                return percent;
+            }
+            let value = percent.map_or(1.0, |percent| percent_to_param(percent, min, max));
+            Some(param_to_percent(
+                (value * factor).clamp(min, ceiling),
+                min,
+                max,
+            ))
+        };
+        Self {
+            pitch: scale(self.pitch, prosody.pitch, PITCH_RANGE, PITCH_RANGE.1),
+            volume: scale(self.volume, prosody.volume, VOLUME_RANGE, MAX_SSML_VOLUME),
+            ..self.clone()
+        }
+    }
+    /// Rescales alignment times to the speed change applied by `rate`
+    fn apply_to_alignment(&self, alignment: &mut [PhonemeAlignment]) {
+        if let Some(rate) = self.rate {
+            let speed = percent_to_param(rate, RATE_RANGE.0, RATE_RANGE.1);
+            for phoneme in alignment.iter_mut() {
+                phoneme.start_ms /= speed;
+                phoneme.end_ms /= speed;
+            }
+        }
+    }
     fn apply_to_raw_samples(
         &self,
         samples: AudioSamples,
@@ -75,9 +127,16 @@ impl AudioOutputConfig {
         if input_len == 0 {
             return Ok(samples.into());
         }
+        let _span = tracing::debug_span!("post_processing", num_samples = input_len).entered();
         let mut out_buf: Vec<f32> = Vec::new();
         unsafe {
             let stream = sonic_rs_sys::sonicCreateStream(sample_rate as i32, num_channels as i32);
+            if stream.is_null() {
+                return Err(PiperError::AudioEncoding {
+                    message: "Sonic Error: failed to create a stream".to_string(),
+                    source: None,
+                });
+            }
             if let Some(rate) = self.rate {
                 sonic_rs_sys::sonicSetSpeed(
                     stream,
@@ -100,9 +159,11 @@ impl AudioOutputConfig {
             sonic_rs_sys::sonicFlushStream(stream);
             let num_samples = sonic_rs_sys::sonicSamplesAvailable(stream);
             if num_samples <= 0 {
-                return Err(
-                    PiperError::OperationError("Sonic Error: failed to apply audio config. Invalid parameter value for rate, volume, or pitch".to_string())
-                );
+                sonic_rs_sys::sonicDestroyStream(stream);
+                return Err(PiperError::AudioEncoding {
+                    message: "Sonic Error: failed to apply audio config. Invalid parameter value for rate, volume, or pitch".to_string(),
+                    source: None,
+                });
             }
             out_buf.reserve_exact(num_samples as usize);
             sonic_rs_sys::sonicReadFloatFromStream(
@@ -113,6 +174,14 @@ impl AudioOutputConfig {
             sonic_rs_sys::sonicDestroyStream(stream);
             out_buf.set_len(num_samples as usize);
         }
+        if self
+            .volume
+            .is_some_and(|volume| percent_to_param(volume, VOLUME_RANGE.0, VOLUME_RANGE.1) > 1.0)
+        {
+            out_buf
+                .iter_mut()
+                .for_each(|sample| *sample = sample.clamp(-1.0, 1.0));
+        }
         Ok(out_buf.into())
     }
     #[inline(always)]
@@ -128,22 +197,109 @@ impl AudioOutputConfig {
     }
 }
 
-pub struct PiperSpeechSynthesizer(Arc<dyn PiperModel + Sync + Send>);
+/// Signals a running synthesis task to stop. Clones share the same state.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+pub struct PiperSpeechSynthesizer {
+    model: Arc<dyn PiperModel + Sync + Send>,
+    stream_capacity: usize,
+    thread_pool: Option<Arc<ThreadPool>>,
+    max_parallelism: Option<usize>,
+    /// Runs parallel synthesis when `max_parallelism` is set, built on first use
+    parallel_pool: OnceCell<ThreadPool>,
+}
 
 impl PiperSpeechSynthesizer {
     pub fn new(model: Arc<dyn PiperModel + Sync + Send>) -> PiperResult<Self> {
-        Ok(Self(model))
+        Ok(Self {
+            model,
+            stream_capacity: DEFAULT_STREAM_CAPACITY,
+            thread_pool: None,
+            max_parallelism: None,
+            parallel_pool: OnceCell::new(),
+        })
+    }
+    /// Runs parallel, realtime and async synthesis on the given pool instead of
+    /// rayon's global pool and [`SYNTHESIS_THREAD_POOL`]
+    pub fn with_thread_pool(mut self, thread_pool: Arc<ThreadPool>) -> Self {
+        self.thread_pool = Some(thread_pool);
+        self
+    }
+    /// Maximum number of sentences parallel synthesis runs at the same time, across all
+    /// calls on this synthesizer. Parallel synthesis then runs on a pool of its own with
+    /// this many threads, instead of the pool given to [`Self::with_thread_pool`].
+    pub fn with_max_parallelism(mut self, max_parallelism: usize) -> Self {
+        self.max_parallelism = Some(max_parallelism.max(1));
+        self.parallel_pool = OnceCell::new();
+        self
+    }
+    /// Maximum number of chunks a realtime stream synthesizes ahead of its consumer.
+    /// Synthesis pauses while the stream is full.
+    pub fn with_stream_capacity(mut self, stream_capacity: usize) -> Self {
+        self.stream_capacity = stream_capacity.max(1);
+        self
+    }
+    pub fn stream_capacity(&self) -> usize {
+        self.stream_capacity
+    }
+    pub fn thread_pool(&self) -> Option<&Arc<ThreadPool>> {
+        self.thread_pool.as_ref()
+    }
+    pub fn max_parallelism(&self) -> Option<usize> {
+        self.max_parallelism
+    }
+    /// Pool that runs background synthesis tasks
+    fn task_pool(&self) -> &ThreadPool {
+        match self.thread_pool {
+            Some(ref thread_pool) => thread_pool,
+            None => &SYNTHESIS_THREAD_POOL,
+        }
+    }
+    /// Pool that runs parallel synthesis, `None` for rayon's global pool
+    fn parallel_pool(&self) -> PiperResult<Option<&ThreadPool>> {
+        let Some(max_parallelism) = self.max_parallelism else {
+            return Ok(self.thread_pool.as_deref());
+        };
+        let thread_pool = self.parallel_pool.get_or_try_init(|| {
+            ThreadPoolBuilder::new()
+                .thread_name(|i| format!("piper_parallel_{}", i))
+                .num_threads(max_parallelism)
+                .build()
+                .map_err(|e| {
+                    PiperError::OperationError(format!(
+                        "Failed to create the parallel synthesis thread pool: {}",
+                        e
+                    ))
+                })
+        })?;
+        Ok(Some(thread_pool))
     }
 
     fn create_synthesis_task_provider(
         &self,
         text: String,
+        synthesis_config: Option<PiperSynthesisConfig>,
         output_config: Option<AudioOutputConfig>,
     ) -> SpeechSynthesisTaskProvider {
         SpeechSynthesisTaskProvider {
             model: self.clone_model(),
             text,
+            synthesis_config,
             output_config,
+            started: Instant::now(),
         }
     }
 
@@ -152,14 +308,72 @@ impl PiperSpeechSynthesizer {
         text: String,
         output_config: Option<AudioOutputConfig>,
     ) -> PiperResult<PiperSpeechStreamLazy> {
-        PiperSpeechStreamLazy::new(self.create_synthesis_task_provider(text, output_config))
+        PiperSpeechStreamLazy::new(self.create_synthesis_task_provider(text, None, output_config))
+    }
+    /// Like [`Self::synthesize_lazy`], but uses the given synthesis config for this request
+    /// only, leaving the shared model untouched.
+    pub fn synthesize_lazy_with_config(
+        &self,
+        text: String,
+        synthesis_config: PiperSynthesisConfig,
+        output_config: Option<AudioOutputConfig>,
+    ) -> PiperResult<PiperSpeechStreamLazy> {
+        PiperSpeechStreamLazy::new(self.create_synthesis_task_provider(
+            text,
+            Some(synthesis_config),
+            output_config,
+        ))
+    }
+    /// Async version of [`Self::synthesize_lazy`]. Sentences are synthesized on the
+    /// synthesis thread pool, so polling the stream never blocks the executor.
+    /// Dropping the stream stops the synthesis after the current sentence.
+    pub fn synthesize_lazy_async(
+        &self,
+        text: String,
+        output_config: Option<AudioOutputConfig>,
+    ) -> AsyncSpeechStream {
+        AsyncSpeechStream::new(
+            self.create_synthesis_task_provider(text, None, output_config),
+            self.task_pool(),
+            self.stream_capacity,
+        )
+    }
+    /// Like [`Self::synthesize_lazy_async`], but uses the given synthesis config for this
+    /// request only, leaving the shared model untouched.
+    pub fn synthesize_lazy_async_with_config(
+        &self,
+        text: String,
+        synthesis_config: PiperSynthesisConfig,
+        output_config: Option<AudioOutputConfig>,
+    ) -> AsyncSpeechStream {
+        AsyncSpeechStream::new(
+            self.create_synthesis_task_provider(text, Some(synthesis_config), output_config),
+            self.task_pool(),
+            self.stream_capacity,
+        )
     }
     pub fn synthesize_parallel(
         &self,
         text: String,
         output_config: Option<AudioOutputConfig>,
     ) -> PiperResult<PiperSpeechStreamParallel> {
-        PiperSpeechStreamParallel::new(self.create_synthesis_task_provider(text, output_config))
+        PiperSpeechStreamParallel::new(
+            self.create_synthesis_task_provider(text, None, output_config),
+            self.parallel_pool()?,
+        )
+    }
+    /// Like [`Self::synthesize_parallel`], but uses the given synthesis config for this
+    /// request only, leaving the shared model untouched.
+    pub fn synthesize_parallel_with_config(
+        &self,
+        text: String,
+        synthesis_config: PiperSynthesisConfig,
+        output_config: Option<AudioOutputConfig>,
+    ) -> PiperResult<PiperSpeechStreamParallel> {
+        PiperSpeechStreamParallel::new(
+            self.create_synthesis_task_provider(text, Some(synthesis_config), output_config),
+            self.parallel_pool()?,
+        )
     }
     pub fn synthesize_streamed(
         &self,
@@ -168,14 +382,64 @@ impl PiperSpeechSynthesizer {
         chunk_size: usize,
         chunk_padding: usize,
     ) -> PiperResult<RealtimeSpeechStream> {
-        let provider = self.create_synthesis_task_provider(text, output_config);
-        let wavinfo = self.0.audio_output_info()?;
-        RealtimeSpeechStream::new(
+        let provider = self.create_synthesis_task_provider(text, None, output_config);
+        self.start_realtime_stream(provider, chunk_size, chunk_padding)
+    }
+    /// Like [`Self::synthesize_streamed`], but uses the given synthesis config for this
+    /// request only, leaving the shared model untouched.
+    pub fn synthesize_streamed_with_config(
+        &self,
+        text: String,
+        synthesis_config: PiperSynthesisConfig,
+        output_config: Option<AudioOutputConfig>,
+        chunk_size: usize,
+        chunk_padding: usize,
+    ) -> PiperResult<RealtimeSpeechStream> {
+        let provider =
+            self.create_synthesis_task_provider(text, Some(synthesis_config), output_config);
+        self.start_realtime_stream(provider, chunk_size, chunk_padding)
+    }
+    fn start_realtime_stream(
+        &self,
+        provider: SpeechSynthesisTaskProvider,
+        chunk_size: usize,
+        chunk_padding: usize,
+    ) -> PiperResult<RealtimeSpeechStream> {
+        let wavinfo = self.model.audio_output_info()?;
+        Ok(RealtimeSpeechStream::new(
             provider,
             chunk_size,
             chunk_padding,
             wavinfo.sample_rate,
             wavinfo.num_channels,
+            self.task_pool(),
+            self.stream_capacity,
+        ))
+    }
+
+    /// Synthesizes an SSML document one sentence at a time, see [`crate::ssml`] for the
+    /// supported elements. Breaks are returned as silent audio.
+    pub fn synthesize_ssml(
+        &self,
+        ssml: &str,
+        output_config: Option<AudioOutputConfig>,
+    ) -> PiperResult<SsmlSpeechStream> {
+        let synthesis_config = self.model.get_fallback_synthesis_config()?;
+        self.synthesize_ssml_with_config(ssml, synthesis_config, output_config)
+    }
+    /// Like [`Self::synthesize_ssml`], but applies the document's prosody and voices on top
+    /// of the given synthesis config instead of the model's fallback config.
+    pub fn synthesize_ssml_with_config(
+        &self,
+        ssml: &str,
+        synthesis_config: PiperSynthesisConfig,
+        output_config: Option<AudioOutputConfig>,
+    ) -> PiperResult<SsmlSpeechStream> {
+        SsmlSpeechStream::new(
+            self.clone_model(),
+            parse_ssml(ssml)?,
+            synthesis_config,
+            output_config,
         )
     }
 
@@ -184,9 +448,38 @@ impl PiperSpeechSynthesizer {
         filename: &Path,
         text: String,
         output_config: Option<AudioOutputConfig>,
+    ) -> PiperResult<()> {
+        let stream = self.synthesize_parallel(text, output_config)?;
+        self.write_stream_to_file(filename, stream)
+    }
+    /// Like [`Self::synthesize_to_file`], but uses the given synthesis config for this
+    /// request only, leaving the shared model untouched.
+    pub fn synthesize_to_file_with_config(
+        &self,
+        filename: &Path,
+        text: String,
+        synthesis_config: PiperSynthesisConfig,
+        output_config: Option<AudioOutputConfig>,
+    ) -> PiperResult<()> {
+        let stream = self.synthesize_parallel_with_config(text, synthesis_config, output_config)?;
+        self.write_stream_to_file(filename, stream)
+    }
+    pub fn synthesize_ssml_to_file(
+        &self,
+        filename: &Path,
+        ssml: &str,
+        output_config: Option<AudioOutputConfig>,
+    ) -> PiperResult<()> {
+        let stream = self.synthesize_ssml(ssml, output_config)?;
+        self.write_stream_to_file(filename, stream)
+    }
+    fn write_stream_to_file(
+        &self,
+        filename: &Path,
+        stream: impl Iterator<Item = PiperAudioResult>,
     ) -> PiperResult<()> {
         let mut samples: Vec<f32> = Vec::new();
-        for result in self.synthesize_parallel(text, output_config)? {
+        for result in stream {
             match result {
                 Ok(ws) => {
                     samples.append(&mut ws.into_vec());
@@ -195,84 +488,176 @@ impl PiperSpeechSynthesizer {
             };
         }
         if samples.is_empty() {
-            return Err(PiperError::OperationError(
-                "No speech data to write".to_string(),
-            ));
+            return Err(PiperError::AudioEncoding {
+                message: "No speech data to write".to_string(),
+                source: None,
+            });
         }
+        let _span = tracing::debug_span!("wav_encoding", num_samples = samples.len()).entered();
         let audio = AudioSamples::from(samples);
+        let info = self.model.audio_output_info()?;
+        let to_u32 = |value: usize, name: &str| {
+            u32::try_from(value).map_err(|_| PiperError::AudioEncoding {
+                message: format!("The {} `{}` is out of range for a wave file", name, value),
+                source: None,
+            })
+        };
         Ok(audio::write_wave_samples_to_file(
             filename,
             audio.to_i16_vec().iter(),
-            self.0.audio_output_info()?.sample_rate as u32,
-            self.0.audio_output_info()?.num_channels.try_into().unwrap(),
-            self.0.audio_output_info()?.sample_width.try_into().unwrap(),
+            to_u32(info.sample_rate, "sample rate")?,
+            to_u32(info.num_channels, "number of channels")?,
+            to_u32(info.sample_width, "sample width")?,
         )?)
     }
     #[inline(always)]
     pub fn clone_model(&self) -> Arc<dyn PiperModel + Send + Sync> {
-        Arc::clone(&self.0)
+        Arc::clone(&self.model)
     }
 }
 
 impl PiperModel for PiperSpeechSynthesizer {
     fn audio_output_info(&self) -> PiperResult<AudioInfo> {
-        self.0.audio_output_info()
+        self.model.audio_output_info()
     }
     fn phonemize_text(&self, text: &str) -> PiperResult<Phonemes> {
-        self.0.phonemize_text(text)
+        self.model.phonemize_text(text)
     }
     fn speak_batch(&self, phoneme_batches: Vec<String>) -> PiperResult<Vec<Audio>> {
-        self.0.speak_batch(phoneme_batches)
+        self.model.speak_batch(phoneme_batches)
     }
     fn speak_one_sentence(&self, phonemes: String) -> PiperAudioResult {
-        self.0.speak_one_sentence(phonemes)
+        self.model.speak_one_sentence(phonemes)
     }
-    fn get_default_synthesis_config(&self) -> PiperResult<Box<dyn Any>> {
-        self.0.get_default_synthesis_config()
+    fn speak_batch_with_config(
+        &self,
+        phoneme_batches: Vec<String>,
+        synthesis_config: &PiperSynthesisConfig,
+    ) -> PiperResult<Vec<Audio>> {
+        self.model
+            .speak_batch_with_config(phoneme_batches, synthesis_config)
+    }
+    fn speak_one_sentence_with_config(
+        &self,
+        phonemes: String,
+        synthesis_config: &PiperSynthesisConfig,
+    ) -> PiperAudioResult {
+        self.model
+            .speak_one_sentence_with_config(phonemes, synthesis_config)
     }
-    fn get_fallback_synthesis_config(&self) -> PiperResult<Box<dyn Any>> {
-        self.0.get_fallback_synthesis_config()
+    fn get_default_synthesis_config(&self) -> PiperResult<PiperSynthesisConfig> {
+        self.model.get_default_synthesis_config()
     }
-    fn set_fallback_synthesis_config(&self, synthesis_config: &dyn Any) -> PiperResult<()> {
-        self.0.set_fallback_synthesis_config(synthesis_config)
+    fn get_fallback_synthesis_config(&self) -> PiperResult<PiperSynthesisConfig> {
+        self.model.get_fallback_synthesis_config()
+    }
+    fn set_fallback_synthesis_config(
+        &self,
+        synthesis_config: &PiperSynthesisConfig,
+    ) -> PiperResult<()> {
+        self.model.set_fallback_synthesis_config(synthesis_config)
     }
     fn get_language(&self) -> PiperResult<Option<String>> {
-        self.0.get_language()
+        self.model.get_language()
     }
     fn get_speakers(&self) -> PiperResult<Option<&HashMap<i64, String>>> {
-        self.0.get_speakers()
+        self.model.get_speakers()
     }
     fn set_speaker(&self, sid: i64) -> Option<PiperError> {
-        self.0.set_speaker(sid)
+        self.model.set_speaker(sid)
     }
     fn properties(&self) -> PiperResult<HashMap<String, String>> {
-        self.0.properties()
+        self.model.properties()
+    }
+    fn get_input_output_info(&self) -> PiperResult<Vec<SessionInfo>> {
+        self.model.get_input_output_info()
+    }
+    fn phonemes_to_ids(&self, phonemes: &str) -> PiperResult<PhonemeIds> {
+        self.model.phonemes_to_ids(phonemes)
+    }
+    fn speaker_embedding(&self, speaker: &Speaker) -> PiperResult<SpeakerEmbedding> {
+        self.model.speaker_embedding(speaker)
+    }
+    fn warm_up(&self) -> PiperResult<WarmUpTimings> {
+        self.model.warm_up()
     }
     fn supports_streaming_output(&self) -> bool {
-        self.0.supports_streaming_output()
+        self.model.supports_streaming_output()
     }
     fn stream_synthesis<'a>(
         &'a self,
         #[allow(unused_variables)] phonemes: String,
         #[allow(unused_variables)] chunk_size: usize,
         #[allow(unused_variables)] chunk_padding: usize,
-    ) -> PiperResult<Box<dyn Iterator<Item = PiperResult<AudioSamples>> + Send + Sync + 'a>> {
-        self.0.stream_synthesis(phonemes, chunk_size, chunk_padding)
+    ) -> PiperResult<AudioStreamIterator<'a>> {
+        self.model
+            .stream_synthesis(phonemes, chunk_size, chunk_padding)
+    }
+    fn stream_synthesis_with_config(
+        &self,
+        phonemes: String,
+        synthesis_config: &PiperSynthesisConfig,
+        chunk_size: usize,
+        chunk_padding: usize,
+    ) -> PiperResult<AudioStreamIterator<'_>> {
+        self.model.stream_synthesis_with_config(
+            phonemes,
+            synthesis_config,
+            chunk_size,
+            chunk_padding,
+        )
+    }
+    fn stream_synthesis_cancellable(
+        &self,
+        phonemes: String,
+        synthesis_config: Option<&PiperSynthesisConfig>,
+        chunk_size: usize,
+        chunk_padding: usize,
+        cancellation: CancellationToken,
+    ) -> PiperResult<AudioStreamIterator<'_>> {
+        self.model.stream_synthesis_cancellable(
+            phonemes,
+            synthesis_config,
+            chunk_size,
+            chunk_padding,
+            cancellation,
+        )
     }
 }
 
 struct SpeechSynthesisTaskProvider {
     model: Arc<dyn PiperModel + Sync + Send>,
     text: String,
+    synthesis_config: Option<PiperSynthesisConfig>,
     output_config: Option<AudioOutputConfig>,
+    started: Instant,
 }
 
 impl SpeechSynthesisTaskProvider {
-    fn get_phonemes(&self) -> PiperResult<Vec<String>> {
-        Ok(self.model.phonemize_text(&self.text)?.to_vec())
+    fn get_phonemes(&self) -> PiperResult<(Vec<String>, RequestTimings)> {
+        let timer = Instant::now();
+        let phonemes = self.model.phonemize_text(&self.text)?.to_vec();
+        let phonemization = timer.elapsed();
+        tracing::debug!(
+            ?phonemization,
+            num_sentences = phonemes.len(),
+            "phonemized text"
+        );
+        Ok((
+            phonemes,
+            RequestTimings {
+                started: self.started,
+                phonemization: Some(phonemization),
+            },
+        ))
     }
     fn process_one_sentence(&self, phonemes: String) -> PiperAudioResult {
-        let wave_samples = self.model.speak_one_sentence(phonemes)?;
+        let wave_samples = match self.synthesis_config {
+            Some(ref synthesis_config) => self
+                .model
+                .speak_one_sentence_with_config(phonemes, synthesis_config)?,
+            None => self.model.speak_one_sentence(phonemes)?,
+        };
         match self.output_config {
             Some(ref config) => config.apply(wave_samples),
             None => Ok(wave_samples),
@@ -280,7 +665,12 @@ impl SpeechSynthesisTaskProvider {
     }
     #[allow(dead_code)]
     fn process_batches(&self, phonemes: Vec<String>) -> PiperResult<Vec<Audio>> {
-        let wave_samples = self.model.speak_batch(phonemes)?;
+        let wave_samples = match self.synthesis_config {
+            Some(ref synthesis_config) => self
+                .model
+                .speak_batch_with_config(phonemes, synthesis_config)?,
+            None => self.model.speak_batch(phonemes)?,
+        };
         match self.output_config {
             Some(ref config) => {
                 let mut processed: Vec<Audio> = Vec::with_capacity(wave_samples.len());
@@ -294,17 +684,34 @@ impl SpeechSynthesisTaskProvider {
     }
 }
 
+/// Timings that belong to a whole request, reported with its first audio
+struct RequestTimings {
+    started: Instant,
+    phonemization: Option<Duration>,
+}
+
+impl RequestTimings {
+    fn apply_to_first(&mut self, timings: &mut SynthesisTimings) {
+        if let Some(phonemization) = self.phonemization.take() {
+            timings.phonemization = Some(phonemization);
+            timings.time_to_first_chunk = Some(self.started.elapsed());
+        }
+    }
+}
+
 pub struct PiperSpeechStreamLazy {
     provider: SpeechSynthesisTaskProvider,
     sentence_phonemes: std::vec::IntoIter<String>,
+    request_timings: RequestTimings,
 }
 
 impl PiperSpeechStreamLazy {
     fn new(provider: SpeechSynthesisTaskProvider) -> PiperResult<Self> {
-        let sentence_phonemes = provider.get_phonemes()?.into_iter();
+        let (phonemes, request_timings) = provider.get_phonemes()?;
         Ok(Self {
             provider,
-            sentence_phonemes,
+            sentence_phonemes: phonemes.into_iter(),
+            request_timings,
         })
     }
 }
@@ -315,24 +722,170 @@ impl Iterator for PiperSpeechStreamLazy {
     fn next(&mut self) -> Option<Self::Item> {
         let phonemes = self.sentence_phonemes.next()?;
         match self.provider.process_one_sentence(phonemes) {
-            Ok(ws) => Some(Ok(ws)),
+            Ok(mut ws) => {
+                self.request_timings.apply_to_first(&mut ws.timings);
+                Some(Ok(ws))
+            }
             Err(e) => Some(Err(e)),
         }
     }
 }
 
+/// Speech of an SSML document, synthesized one sentence at a time with the prosody and
+/// voice of its elements
+pub struct SsmlSpeechStream {
+    model: Arc<dyn PiperModel + Sync + Send>,
+    segments: std::vec::IntoIter<SsmlSegment>,
+    synthesis_config: PiperSynthesisConfig,
+    output_config: Option<AudioOutputConfig>,
+    sample_rate: usize,
+    /// Provider of the current speech segment and its remaining sentences
+    current: Option<(SpeechSynthesisTaskProvider, std::vec::IntoIter<String>)>,
+}
+
+impl SsmlSpeechStream {
+    fn new(
+        model: Arc<dyn PiperModel + Sync + Send>,
+        segments: Vec<SsmlSegment>,
+        synthesis_config: PiperSynthesisConfig,
+        output_config: Option<AudioOutputConfig>,
+    ) -> PiperResult<Self> {
+        let sample_rate = model.audio_output_info()?.sample_rate;
+        Ok(Self {
+            model,
+            segments: segments.into_iter(),
+            synthesis_config,
+            output_config,
+            sample_rate,
+            current: None,
+        })
+    }
+    fn start_speech(
+        &self,
+        pieces: Vec<SsmlPiece>,
+        prosody: Prosody,
+        voice: Option<String>,
+    ) -> PiperResult<(SpeechSynthesisTaskProvider, std::vec::IntoIter<String>)> {
+        let mut sentences: Vec<String> = Vec::new();
+        let mut continues_sentence = false;
+        for piece in pieces {
+            let (piece_sentences, ends_sentence) = match piece {
+                SsmlPiece::Text(text) => (
+                    self.model.phonemize_text(&text)?.to_vec(),
+                    ends_sentence(&text),
+                ),
+                SsmlPiece::Phonemes(phonemes) => {
+                    let ends_sentence = ends_sentence(&phonemes);
+                    (vec![phonemes], ends_sentence)
+                }
+            };
+            // A piece continues the sentence the previous piece left open
+            for (index, sentence) in piece_sentences.into_iter().enumerate() {
+                match sentences.last_mut() {
+                    Some(last) if index == 0 && continues_sentence => {
+                        last.push(' ');
+                        last.push_str(&sentence);
+                    }
+                    _ => sentences.push(sentence),
+                }
+            }
+            if let Some(ends_sentence) = ends_sentence {
+                continues_sentence = !ends_sentence;
+            }
+        }
+        sentences.retain(|sentence| !sentence.trim().is_empty());
+        let synthesis_config = PiperSynthesisConfig {
+            speaker: voice
+                .map(Speaker::Name)
+                .or_else(|| self.synthesis_config.speaker.clone()),
+            length_scale: self.synthesis_config.length_scale / prosody.rate.max(MIN_SSML_RATE),
+            ..self.synthesis_config.clone()
+        };
+        let output_config = if prosody.pitch == 1.0 && prosody.volume == 1.0 {
+            self.output_config.clone()
+        } else {
+            Some(
+                self.output_config
+                    .clone()
+                    .unwrap_or_default()
+                    .with_prosody(prosody),
+            )
+        };
+        let provider = SpeechSynthesisTaskProvider {
+            model: Arc::clone(&self.model),
+            text: String::new(),
+            synthesis_config: Some(synthesis_config),
+            output_config,
+            started: Instant::now(),
+        };
+        Ok((provider, sentences.into_iter()))
+    }
+}
+
+/// Whether a piece of SSML text ends with a sentence terminator, `None` if it is blank
+fn ends_sentence(text: &str) -> Option<bool> {
+    let text = text.trim_end();
+    (!text.is_empty()).then(|| text.ends_with(['.', '!', '?']))
+}
+
+impl Iterator for SsmlSpeechStream {
+    type Item = PiperAudioResult;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((provider, sentences)) = self.current.as_mut() {
+                if let Some(phonemes) = sentences.next() {
+                    return Some(provider.process_one_sentence(phonemes));
+                }
+                self.current = None;
+            }
+            match self.segments.next()? {
+                SsmlSegment::Break(duration) => {
+                    let num_samples = duration.as_millis() as usize * self.sample_rate / 1000;
+                    if num_samples > 0 {
+                        let silence = vec![0f32; num_samples];
+                        return Some(Ok(Audio::new(silence.into(), self.sample_rate, None)));
+                    }
+                }
+                SsmlSegment::Speech {
+                    pieces,
+                    prosody,
+                    voice,
+                } => match self.start_speech(pieces, prosody, voice) {
+                    Ok(current) => self.current = Some(current),
+                    Err(e) => return Some(Err(e)),
+                },
+            }
+        }
+    }
+}
+
 #[must_use]
 pub struct PiperSpeechStreamParallel {
     precalculated_results: std::vec::IntoIter<PiperAudioResult>,
 }
 
 impl PiperSpeechStreamParallel {
-    fn new(provider: SpeechSynthesisTaskProvider) -> PiperResult<Self> {
-        let calculated_result: Vec<PiperAudioResult> = provider
-            .get_phonemes()?
-            .par_iter()
-            .map(|ph| provider.process_one_sentence(ph.to_string()))
-            .collect();
+    /// Synthesizes all sentences on `thread_pool`, or on rayon's global pool if `None`
+    fn new(
+        provider: SpeechSynthesisTaskProvider,
+        thread_pool: Option<&ThreadPool>,
+    ) -> PiperResult<Self> {
+        let (phonemes, request_timings) = provider.get_phonemes()?;
+        let synthesize = || -> Vec<PiperAudioResult> {
+            phonemes
+                .par_iter()
+                .map(|ph| provider.process_one_sentence(ph.to_string()))
+                .collect()
+        };
+        let mut calculated_result = match thread_pool {
+            Some(thread_pool) => thread_pool.install(synthesize),
+            None => synthesize(),
+        };
+        // All sentences are ready at the same time, so there is no time to first chunk
+        if let Some(Ok(audio)) = calculated_result.first_mut() {
+            audio.timings.phonemization = request_timings.phonemization;
+        }
         Ok(Self {
             precalculated_results: calculated_result.into_iter(),
         })
@@ -347,7 +900,92 @@ impl Iterator for PiperSpeechStreamParallel {
     }
 }
 
-pub struct RealtimeSpeechStream(Receiver<PiperResult<AudioSamples>>);
+/// Sentences synthesized one at a time in the background, consumed as a [`Stream`].
+/// A cancelled stream yields [`PiperError::Cancelled`] once, then ends.
+#[must_use]
+pub struct AsyncSpeechStream {
+    stream: RecvStream<'static, PiperAudioResult>,
+    cancellation: CancellationToken,
+    cancellation_reported: bool,
+}
+
+impl AsyncSpeechStream {
+    fn new(
+        provider: SpeechSynthesisTaskProvider,
+        thread_pool: &ThreadPool,
+        capacity: usize,
+    ) -> Self {
+        let (tx, rx) = flume::bounded(capacity);
+        let cancellation = CancellationToken::new();
+        let sender = StreamSender {
+            tx,
+            cancellation: cancellation.clone(),
+        };
+        thread_pool.spawn(move || {
+            let (phonemes, mut request_timings) = match provider.get_phonemes() {
+                Ok(phonemes) => phonemes,
+                Err(e) => {
+                    sender.send(Err(e));
+                    return;
+                }
+            };
+            for ph_sent in phonemes {
+                if sender.cancellation.is_cancelled() {
+                    return;
+                }
+                let result = provider.process_one_sentence(ph_sent).map(|mut audio| {
+                    request_timings.apply_to_first(&mut audio.timings);
+                    audio
+                });
+                if sender.send(result).is_none() {
+                    return;
+                }
+            }
+        });
+        Self {
+            stream: rx.into_stream(),
+            cancellation,
+            cancellation_reported: false,
+        }
+    }
+    pub fn cancel(&self) {
+        self.cancellation.cancel();
+    }
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancellation.clone()
+    }
+}
+
+impl Stream for AsyncSpeechStream {
+    type Item = PiperAudioResult;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.cancellation.is_cancelled() {
+            return Poll::Ready(report_cancellation(&mut self.cancellation_reported));
+        }
+        Pin::new(&mut self.stream).poll_next(cx)
+    }
+}
+
+impl Drop for AsyncSpeechStream {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+/// Audio chunks synthesized in the background.
+///
+/// Consume it either as a blocking [`Iterator`] or, from async code, as a [`Stream`].
+/// At most the synthesizer's stream capacity of chunks is buffered ahead of the consumer.
+/// Synthesis stops between decoder chunks once the stream is cancelled or dropped.
+/// A cancelled stream yields [`PiperError::Cancelled`] once, then ends.
+#[must_use]
+pub struct RealtimeSpeechStream {
+    receiver: Receiver<PiperResult<AudioChunk>>,
+    stream: RecvStream<'static, PiperResult<AudioChunk>>,
+    cancellation: CancellationToken,
+    cancellation_reported: bool,
+}
 
 impl RealtimeSpeechStream {
     fn new(
@@ -356,90 +994,682 @@ impl RealtimeSpeechStream {
         chunk_padding: usize,
         sample_rate: usize,
         num_channels: usize,
-    ) -> PiperResult<Self> {
-        let phonemes = provider.get_phonemes()?.into_iter();
-        let (tx, rx) = flume::unbounded();
-        SYNTHESIS_THREAD_POOL.spawn(move || {
+        thread_pool: &ThreadPool,
+        capacity: usize,
+    ) -> Self {
+        let (tx, rx) = flume::bounded(capacity);
+        let cancellation = CancellationToken::new();
+        let task_cancellation = cancellation.clone();
+        thread_pool.spawn(move || {
+            let sender = StreamSender {
+                tx,
+                cancellation: task_cancellation,
+            };
+            let (phonemes, mut request_timings) = match provider.get_phonemes() {
+                Ok(phonemes) => phonemes,
+                Err(e) => {
+                    sender.send(Err(e));
+                    return;
+                }
+            };
             let mut chunk_size = chunk_size;
             let chunk_factor = 1;
             let mut num_processed_chunks = 0;
             for ph_sent in phonemes {
+                if sender.cancellation.is_cancelled() {
+                    return;
+                }
                 chunk_size = if num_processed_chunks != 0 {
                     chunk_size * chunk_factor * num_processed_chunks
                 } else {
                     chunk_size
                 };
-                match provider
-                    .model
-                    .stream_synthesis(ph_sent, chunk_size, chunk_padding)
-                {
+                let stream = provider.model.stream_synthesis_cancellable(
+                    ph_sent,
+                    provider.synthesis_config.as_ref(),
+                    chunk_size,
+                    chunk_padding,
+                    sender.cancellation.clone(),
+                );
+                match stream {
                     Ok(stream) => {
                         let send_result = RealtimeSpeechStream::process_rt_stream(
                             stream,
-                            &tx,
+                            &sender,
+                            &mut request_timings,
                             provider.output_config.as_ref(),
                             sample_rate,
                             num_channels,
                         );
                         match send_result {
-                            Ok(num_chunks) => num_processed_chunks += num_chunks,
-                            Err(_) => return,
+                            Some(num_chunks) => num_processed_chunks += num_chunks,
+                            None => return,
                         };
                     }
                     Err(e) => {
-                        tx.send(Err(e)).ok();
+                        sender.send(Err(e));
                         return;
                     }
                 };
             }
         });
-        Ok(Self(rx))
+        Self {
+            stream: rx.clone().into_stream(),
+            receiver: rx,
+            cancellation,
+            cancellation_reported: false,
+        }
+    }
+    /// Stops the background synthesis. Chunks that were already buffered are discarded
+    /// and the next item is [`PiperError::Cancelled`].
+    pub fn cancel(&self) {
+        self.cancellation.cancel();
+    }
+    /// A token that cancels this stream, e.g. from another thread
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancellation.clone()
     }
+    /// Streams are consumed lazily, so checking for cancellation before pulling the next
+    /// chunk from `stream` stops the decoder between chunks.
+    /// Returns `None` once the stream was cancelled or dropped.
     #[inline(always)]
     fn process_rt_stream(
-        stream: AudioStreamIterator,
-        tx: &Sender<PiperResult<AudioSamples>>,
+        mut stream: AudioStreamIterator,
+        sender: &StreamSender<PiperResult<AudioChunk>>,
+        request_timings: &mut RequestTimings,
         audio_output_config: Option<&AudioOutputConfig>,
         sample_rate: usize,
         num_channels: usize,
-    ) -> Result<usize, SendError<PiperResult<AudioSamples>>> {
+    ) -> Option<usize> {
         let mut num_chunks = 0;
-        if let Some(output_config) = audio_output_config {
-            for result in stream {
-                match result {
-                    Ok(samples) => {
-                        tx.send(output_config.apply_to_raw_samples(
-                            samples,
-                            sample_rate,
-                            num_channels,
-                        ))?;
-                        num_chunks += 1;
-                    }
-                    Err(e) => {
-                        tx.send(Err(e))?;
-                    }
-                };
+        loop {
+            if sender.cancellation.is_cancelled() {
+                return None;
             }
+            let Some(mut result) = stream.next() else {
+                break;
+            };
+            let Some(output_config) = audio_output_config else {
+                if let Ok(chunk) = result.as_mut() {
+                    request_timings.apply_to_first(&mut chunk.timings);
+                }
+                sender.send(result)?;
+                num_chunks += 1;
+                continue;
+            };
+            match result {
+                Ok(mut chunk) => {
+                    if let Some(alignment) = chunk.alignment.as_mut() {
+                        output_config.apply_to_alignment(alignment);
+                    }
+                    let AudioChunk {
+                        samples,
+                        alignment,
+                        mut timings,
+                    } = chunk;
+                    let timer = Instant::now();
+                    let samples =
+                        output_config.apply_to_raw_samples(samples, sample_rate, num_channels);
+                    timings.post_processing = Some(timer.elapsed());
+                    request_timings.apply_to_first(&mut timings);
+                    sender.send(samples.map(|samples| AudioChunk {
+                        samples,
+                        alignment,
+                        timings,
+                    }))?;
+                    num_chunks += 1;
+                }
+                Err(e) => {
+                    sender.send(Err(e))?;
+                }
+            };
+        }
+        // The stream itself ends early when cancelled
+        if sender.cancellation.is_cancelled() {
+            return None;
+        }
+        if let Some(output_config) = audio_output_config {
             if let Some(silence_ms) = output_config.appended_silence_ms {
                 let silence_result =
                     output_config.generate_silence(silence_ms as usize, sample_rate, num_channels);
-                tx.send(silence_result)?;
-            }
-            Ok(num_chunks)
-        } else {
-            for result in stream {
-                tx.send(result)?;
-                num_chunks += 1;
+                sender.send(silence_result.map(AudioChunk::from))?;
             }
-            Ok(num_chunks)
         }
+        Some(num_chunks)
     }
 }
 
 impl Iterator for RealtimeSpeechStream {
-    type Item = PiperResult<AudioSamples>;
+    type Item = PiperResult<AudioChunk>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.0.recv().ok()
+        if self.cancellation.is_cancelled() {
+            return report_cancellation(&mut self.cancellation_reported);
+        }
+        self.receiver.recv().ok()
+    }
+}
+
+impl Stream for RealtimeSpeechStream {
+    type Item = PiperResult<AudioChunk>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.cancellation.is_cancelled() {
+            return Poll::Ready(report_cancellation(&mut self.cancellation_reported));
+        }
+        Pin::new(&mut self.stream).poll_next(cx)
+    }
+}
+
+impl Drop for RealtimeSpeechStream {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+/// Item of a cancelled stream: [`PiperError::Cancelled`] the first time, then the end of the stream
+fn report_cancellation<T>(reported: &mut bool) -> Option<PiperResult<T>> {
+    if std::mem::replace(reported, true) {
+        None
+    } else {
+        Some(Err(PiperError::Cancelled))
+    }
+}
+
+/// Producer side of a [`RealtimeSpeechStream`] or an [`AsyncSpeechStream`]
+struct StreamSender<T> {
+    tx: Sender<T>,
+    cancellation: CancellationToken,
+}
+
+impl<T> StreamSender<T> {
+    /// Waits for room in the channel, giving up once the stream is cancelled or dropped.
+    /// Returns `None` if the chunk was not sent.
+    fn send(&self, mut item: T) -> Option<()> {
+        loop {
+            if self.cancellation.is_cancelled() {
+                return None;
+            }
+            match self.tx.send_timeout(item, CANCELLATION_POLL_INTERVAL) {
+                Ok(()) => return Some(()),
+                Err(SendTimeoutError::Timeout(unsent)) => item = unsent,
+                Err(SendTimeoutError::Disconnected(_)) => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Mutex;
+    use std::task::{Wake, Waker};
+    use std::thread::{self, Thread, ThreadId};
+
+    /// Speaks one silent sample per phoneme and streams every sentence endlessly.
+    /// Sentences end at periods.
+    #[derive(Default)]
+    struct FakeModel {
+        phonemizer_thread: Mutex<Option<ThreadId>>,
+        /// Each sentence spoken and the config it was spoken with
+        spoken: Mutex<Vec<(String, PiperSynthesisConfig)>>,
+    }
+
+    impl PiperModel for FakeModel {
+        fn audio_output_info(&self) -> PiperResult<AudioInfo> {
+            Ok(AudioInfo {
+                sample_rate: 1000,
+                num_channels: 1,
+                sample_width: 2,
+            })
+        }
+        fn phonemize_text(&self, text: &str) -> PiperResult<Phonemes> {
+            *self.phonemizer_thread.lock().unwrap() = Some(thread::current().id());
+            if text.is_empty() {
+                return Err(PiperError::with_message("Nothing to phonemize"));
+            }
+            let sentences = text.split('.').map(str::trim).filter(|s| !s.is_empty());
+            Ok(Vec::from_iter(sentences.map(str::to_string)).into())
+        }
+        fn speak_batch(&self, phoneme_batches: Vec<String>) -> PiperResult<Vec<Audio>> {
+            self.speak_batch_with_config(phoneme_batches, &PiperSynthesisConfig::default())
+        }
+        fn speak_one_sentence(&self, phonemes: String) -> PiperAudioResult {
+            self.speak_one_sentence_with_config(phonemes, &PiperSynthesisConfig::default())
+        }
+        fn speak_batch_with_config(
+            &self,
+            phoneme_batches: Vec<String>,
+            synthesis_config: &PiperSynthesisConfig,
+        ) -> PiperResult<Vec<Audio>> {
+            phoneme_batches
+                .into_iter()
+                .map(|phonemes| self.speak_one_sentence_with_config(phonemes, synthesis_config))
+                .collect()
+        }
+        fn speak_one_sentence_with_config(
+            &self,
+            phonemes: String,
+            synthesis_config: &PiperSynthesisConfig,
+        ) -> PiperAudioResult {
+            let samples = vec![0.0; phonemes.chars().count()];
+            let spoken = (phonemes, synthesis_config.clone());
+            self.spoken.lock().unwrap().push(spoken);
+            Ok(Audio::new(samples.into(), 1000, None))
+        }
+        fn get_default_synthesis_config(&self) -> PiperResult<PiperSynthesisConfig> {
+            Ok(PiperSynthesisConfig::default())
+        }
+        fn get_fallback_synthesis_config(&self) -> PiperResult<PiperSynthesisConfig> {
+            Ok(PiperSynthesisConfig::default())
+        }
+        fn set_fallback_synthesis_config(&self, _: &PiperSynthesisConfig) -> PiperResult<()> {
+            Ok(())
+        }
+        fn set_speaker(&self, _: i64) -> Option<PiperError> {
+            None
+        }
+        fn supports_streaming_output(&self) -> bool {
+            true
+        }
+        fn stream_synthesis(
+            &self,
+            _phonemes: String,
+            chunk_size: usize,
+            _chunk_padding: usize,
+        ) -> PiperResult<AudioStreamIterator<'_>> {
+            Ok(Box::new(std::iter::repeat_with(move || {
+                Ok(AudioChunk::from(AudioSamples::from(vec![0.0; chunk_size])))
+            })))
+        }
+        fn stream_synthesis_with_config(
+            &self,
+            phonemes: String,
+            _synthesis_config: &PiperSynthesisConfig,
+            chunk_size: usize,
+            chunk_padding: usize,
+        ) -> PiperResult<AudioStreamIterator<'_>> {
+            self.stream_synthesis(phonemes, chunk_size, chunk_padding)
+        }
+    }
+
+    /// Implements only the required methods and plain streaming, like models written
+    /// before synthesis configs could be given per call
+    #[derive(Default)]
+    struct MinimalModel(FakeModel);
+
+    impl PiperModel for MinimalModel {
+        fn audio_output_info(&self) -> PiperResult<AudioInfo> {
+            self.0.audio_output_info()
+        }
+        fn phonemize_text(&self, text: &str) -> PiperResult<Phonemes> {
+            self.0.phonemize_text(text)
+        }
+        fn speak_batch(&self, phoneme_batches: Vec<String>) -> PiperResult<Vec<Audio>> {
+            self.0.speak_batch(phoneme_batches)
+        }
+        fn speak_one_sentence(&self, phonemes: String) -> PiperAudioResult {
+            self.0.speak_one_sentence(phonemes)
+        }
+        fn get_default_synthesis_config(&self) -> PiperResult<PiperSynthesisConfig> {
+            Ok(PiperSynthesisConfig {
+                length_scale: 1.5,
+                ..Default::default()
+            })
+        }
+        fn set_fallback_synthesis_config(&self, _: &PiperSynthesisConfig) -> PiperResult<()> {
+            Ok(())
+        }
+        fn set_speaker(&self, _: i64) -> Option<PiperError> {
+            None
+        }
+        fn supports_streaming_output(&self) -> bool {
+            true
+        }
+        fn stream_synthesis(
+            &self,
+            phonemes: String,
+            chunk_size: usize,
+            chunk_padding: usize,
+        ) -> PiperResult<AudioStreamIterator<'_>> {
+            self.0.stream_synthesis(phonemes, chunk_size, chunk_padding)
+        }
+    }
+
+    /// Takes a while to speak each sentence and records how many were spoken at once
+    #[derive(Default)]
+    struct SlowModel {
+        model: FakeModel,
+        running: AtomicUsize,
+        peak_running: AtomicUsize,
+    }
+
+    impl PiperModel for SlowModel {
+        fn audio_output_info(&self) -> PiperResult<AudioInfo> {
+            self.model.audio_output_info()
+        }
+        fn phonemize_text(&self, text: &str) -> PiperResult<Phonemes> {
+            self.model.phonemize_text(text)
+        }
+        fn speak_batch(&self, phoneme_batches: Vec<String>) -> PiperResult<Vec<Audio>> {
+            self.model.speak_batch(phoneme_batches)
+        }
+        fn speak_one_sentence(&self, phonemes: String) -> PiperAudioResult {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak_running.fetch_max(running, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(10));
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            self.model.speak_one_sentence(phonemes)
+        }
+        fn get_default_synthesis_config(&self) -> PiperResult<PiperSynthesisConfig> {
+            self.model.get_default_synthesis_config()
+        }
+        fn set_fallback_synthesis_config(&self, _: &PiperSynthesisConfig) -> PiperResult<()> {
+            Ok(())
+        }
+        fn set_speaker(&self, _: i64) -> Option<PiperError> {
+            None
+        }
+    }
+
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    /// Polls a stream like an executor would, parking the thread until the stream wakes it
+    fn poll_next_item<S: Stream + Unpin>(stream: &mut S) -> Option<S::Item> {
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            match Pin::new(&mut *stream).poll_next(&mut cx) {
+                Poll::Ready(item) => return item,
+                Poll::Pending => {
+                    assert!(Instant::now() < deadline, "the stream was never woken");
+                    thread::park_timeout(Duration::from_secs(1));
+                }
+            }
+        }
+    }
+
+    /// Waits until the background task of a dropped stream released the model
+    fn wait_for_producer_exit(model: &Arc<FakeModel>) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while Arc::strong_count(model) > 1 {
+            assert!(
+                Instant::now() < deadline,
+                "the producer kept running after its stream was dropped"
+            );
+            thread::sleep(CANCELLATION_POLL_INTERVAL);
+        }
+    }
+
+    #[test]
+    fn test_dropping_realtime_stream_stops_producer() {
+        let model = Arc::new(FakeModel::default());
+        let synth = PiperSpeechSynthesizer::new(model.clone())
+            .unwrap()
+            .with_stream_capacity(1);
+        let mut stream = synth
+            .synthesize_streamed("ab".to_string(), None, 4, 1)
+            .unwrap();
+        drop(synth);
+        for _ in 0..3 {
+            assert_eq!(poll_next_item(&mut stream).unwrap().unwrap().len(), 4);
+        }
+        let phonemizer_thread = *model.phonemizer_thread.lock().unwrap();
+        assert!(phonemizer_thread.is_some_and(|id| id != thread::current().id()));
+        // The fake model streams endlessly, only the cancellation ends its producer
+        drop(stream);
+        wait_for_producer_exit(&model);
+    }
+
+    #[test]
+    fn test_dropping_async_stream_stops_producer() {
+        let model = Arc::new(FakeModel::default());
+        let synth = PiperSpeechSynthesizer::new(model.clone())
+            .unwrap()
+            .with_stream_capacity(1);
+        let mut stream = synth.synthesize_lazy_async(vec!["ab"; 10_000].join("."), None);
+        drop(synth);
+        for _ in 0..3 {
+            assert_eq!(poll_next_item(&mut stream).unwrap().unwrap().len(), 2);
+        }
+        drop(stream);
+        wait_for_producer_exit(&model);
+    }
+
+    #[test]
+    fn test_cancelled_streams_report_it_once() {
+        let synth = PiperSpeechSynthesizer::new(Arc::new(FakeModel::default()))
+            .unwrap()
+            .with_stream_capacity(1);
+        let mut stream = synth
+            .synthesize_streamed("ab".to_string(), None, 4, 1)
+            .unwrap();
+        assert!(stream.next().unwrap().is_ok());
+        stream.cancel();
+        assert!(matches!(stream.next(), Some(Err(PiperError::Cancelled))));
+        assert!(stream.next().is_none());
+        let mut stream = synth.synthesize_lazy_async("ab.ab".to_string(), None);
+        stream.cancel();
+        assert!(matches!(
+            poll_next_item(&mut stream),
+            Some(Err(PiperError::Cancelled))
+        ));
+        assert!(poll_next_item(&mut stream).is_none());
+    }
+
+    #[test]
+    fn test_ssml_speech_stream() {
+        let model = Arc::new(FakeModel::default());
+        let synth = PiperSpeechSynthesizer::new(model.clone()).unwrap();
+        let synthesis_config = PiperSynthesisConfig {
+            length_scale: 1.0,
+            ..Default::default()
+        };
+        let stream = synth
+            .synthesize_ssml_with_config(
+                r#"<speak>One. <break time="250ms"/>
+                <prosody rate="50%">Two <phoneme ph="tuː">two</phoneme>. <phoneme ph="θriː">three</phoneme></prosody>
+                <voice name="b">Four</voice></speak>"#,
+                synthesis_config,
+                None,
+            )
+            .unwrap();
+        // The fake model speaks one sample per phoneme at 1000 Hz
+        let lengths = Vec::from_iter(stream.map(|audio| audio.unwrap().len()));
+        assert_eq!(lengths, vec![3, 250, 7, 4, 4]);
+        let spoken = model.spoken.lock().unwrap();
+        let phonemes = Vec::from_iter(spoken.iter().map(|(phonemes, _)| phonemes.as_str()));
+        // Only a piece that leaves its sentence open is joined with the next one
+        assert_eq!(phonemes, ["One", "Two tuː", "θriː", "Four"]);
+        let length_scales = Vec::from_iter(spoken.iter().map(|(_, config)| config.length_scale));
+        assert_eq!(length_scales, [1.0, 2.0, 2.0, 1.0]);
+        let speakers = Vec::from_iter(spoken.iter().map(|(_, config)| config.speaker.clone()));
+        assert_eq!(
+            speakers,
+            [None, None, None, Some(Speaker::Name("b".to_string()))]
+        );
+    }
+
+    #[test]
+    fn test_loud_prosody_amplifies_and_clips() {
+        let loud = Prosody {
+            volume: 1.5,
+            ..Default::default()
+        };
+        let x_loud = Prosody {
+            volume: 2.0,
+            ..Default::default()
+        };
+        let config = AudioOutputConfig::default();
+        assert_eq!(config.with_prosody(loud).volume, Some(150));
+        assert_eq!(config.with_prosody(x_loud).volume, Some(200));
+        let quiet = AudioOutputConfig {
+            volume: Some(50),
+            ..Default::default()
+        };
+        assert_eq!(quiet.with_prosody(loud).volume, Some(75));
+        // Gains are capped at `x-loud`
+        let louder = AudioOutputConfig {
+            volume: Some(150),
+            ..Default::default()
+        };
+        assert_eq!(louder.with_prosody(x_loud).volume, Some(200));
+        let samples = config
+            .with_prosody(x_loud)
+            .apply_to_raw_samples(vec![0.8, -0.8, 0.25].into(), 1000, 1)
+            .unwrap()
+            .into_vec();
+        assert!(
+            samples.iter().all(|sample| sample.abs() <= 1.0),
+            "{:?}",
+            samples
+        );
+    }
+
+    #[test]
+    fn test_phonemization_errors_are_streamed() {
+        let model = Arc::new(FakeModel::default());
+        let synth = PiperSpeechSynthesizer::new(model.clone()).unwrap();
+        let mut stream = synth
+            .synthesize_streamed(String::new(), None, 4, 1)
+            .unwrap();
+        assert!(poll_next_item(&mut stream).unwrap().is_err());
+        assert!(poll_next_item(&mut stream).is_none());
+        let mut stream = synth.synthesize_lazy_async(String::new(), None);
+        assert!(poll_next_item(&mut stream).unwrap().is_err());
+        assert!(poll_next_item(&mut stream).is_none());
+    }
+
+    #[test]
+    fn test_models_with_only_required_methods() {
+        let model = Arc::new(MinimalModel::default());
+        let fallback = model.get_fallback_synthesis_config().unwrap();
+        assert_eq!(fallback.length_scale, 1.5);
+        assert!(matches!(
+            model.speak_one_sentence_with_config("One".to_string(), &fallback),
+            Err(PiperError::OperationError(_))
+        ));
+        let synth = PiperSpeechSynthesizer::new(model).unwrap();
+        let lengths = Vec::from_iter(
+            synth
+                .synthesize_parallel("One. Three.".to_string(), None)
+                .unwrap()
+                .map(|audio| audio.unwrap().samples.len()),
+        );
+        assert_eq!(lengths, vec![3, 5]);
+        // Streams without a config do not need `stream_synthesis_with_config`
+        let mut stream = synth
+            .synthesize_streamed("One.".to_string(), None, 4, 1)
+            .unwrap();
+        assert_eq!(stream.next().unwrap().unwrap().samples.len(), 4);
+    }
+
+    #[test]
+    fn test_max_parallelism_is_shared_by_all_calls() {
+        let model = Arc::new(SlowModel::default());
+        let synth = PiperSpeechSynthesizer::new(model.clone())
+            .unwrap()
+            .with_max_parallelism(2);
+        let text = "One. Two. Three. Four. Five. Six.";
+        thread::scope(|scope| {
+            for _ in 0..3 {
+                scope.spawn(|| {
+                    let audio = synth.synthesize_parallel(text.to_string(), None).unwrap();
+                    assert_eq!(audio.count(), 6);
+                });
+            }
+        });
+        assert_eq!(model.peak_running.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_send_to_full_stream_stops_on_cancel() {
+        let (tx, rx) = flume::bounded::<PiperResult<AudioChunk>>(1);
+        let sender = StreamSender {
+            tx,
+            cancellation: CancellationToken::new(),
+        };
+        assert!(sender.send(Ok(AudioChunk::default())).is_some());
+        let cancellation = sender.cancellation.clone();
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            cancellation.cancel();
+        });
+        // Blocks until cancelled, since nobody reads from the full channel
+        assert!(sender.send(Ok(AudioChunk::default())).is_none());
+        canceller.join().unwrap();
+        assert_eq!(rx.len(), 1);
+        drop(rx);
+        assert!(sender.send(Ok(AudioChunk::default())).is_none());
+    }
+
+    #[test]
+    fn test_no_chunk_is_decoded_after_cancel() {
+        let cancellation = CancellationToken::new();
+        let decoded = Arc::new(AtomicUsize::new(0));
+        // Cancels while decoding its third chunk
+        let chunks = {
+            let cancellation = cancellation.clone();
+            let decoded = Arc::clone(&decoded);
+            std::iter::repeat_with(move || {
+                if decoded.fetch_add(1, Ordering::Relaxed) == 2 {
+                    cancellation.cancel();
+                }
+                Ok(AudioChunk::from(AudioSamples::from(vec![0.0; 4])))
+            })
+        };
+        let (tx, rx) = flume::unbounded();
+        let sender = StreamSender {
+            tx,
+            cancellation: cancellation.clone(),
+        };
+        let mut request_timings = RequestTimings {
+            started: Instant::now(),
+            phonemization: None,
+        };
+        let sent = RealtimeSpeechStream::process_rt_stream(
+            Box::new(chunks),
+            &sender,
+            &mut request_timings,
+            None,
+            1000,
+            1,
+        );
+        assert_eq!(sent, None);
+        assert_eq!(decoded.load(Ordering::Relaxed), 3);
+        assert_eq!(rx.len(), 2);
+
+        // Models without their own support stop pulling chunks once cancelled
+        let model = FakeModel::default();
+        let cancellation = CancellationToken::new();
+        let mut stream = model
+            .stream_synthesis_cancellable("One".to_string(), None, 4, 0, cancellation.clone())
+            .unwrap();
+        assert!(stream.next().is_some());
+        cancellation.cancel();
+        assert!(stream.next().is_none());
+    }
+
+    #[test]
+    fn test_request_timings_go_to_first_audio() {
+        let mut request_timings = RequestTimings {
+            started: Instant::now(),
+            phonemization: Some(Duration::from_millis(3)),
+        };
+        let mut first = SynthesisTimings::default();
+        let mut second = SynthesisTimings::default();
+        request_timings.apply_to_first(&mut first);
+        request_timings.apply_to_first(&mut second);
+        assert_eq!(first.phonemization, Some(Duration::from_millis(3)));
+        assert!(first.time_to_first_chunk.is_some());
+        assert_eq!(second, SynthesisTimings::default());
     }
 }
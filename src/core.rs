@@ -1,20 +1,124 @@
+use espeak_rs::ESpeakError;
 use std::any::Any;
 use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
 
-pub use crate::audio::{Audio, AudioInfo, AudioSamples, WaveWriterError};
+use crate::audio::synth::CancellationToken;
+pub use crate::audio::{
+    Audio, AudioChunk, AudioInfo, AudioSamples, PhonemeAlignment, SynthesisTimings, WaveWriterError,
+};
+use crate::model_info::SessionInfo;
+use crate::phoneme_ids::PhonemeIds;
+use crate::speaker_embedding::SpeakerEmbedding;
 
 pub type PiperResult<T> = Result<T, PiperError>;
 pub type PiperAudioResult = PiperResult<Audio>;
 pub type AudioStreamIterator<'a> =
-    Box<dyn Iterator<Item = PiperResult<AudioSamples>> + Send + Sync + 'a>;
+    Box<dyn Iterator<Item = PiperResult<AudioChunk>> + Send + Sync + 'a>;
 
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum PiperError {
     FailedToLoadResource(String),
     PhonemizationError(String),
     OperationError(String),
+    /// The model config was parsed but failed validation
+    InvalidConfig(Vec<ConfigIssue>),
+    /// A JSON file could not be parsed. `path` is `None` for data loaded from memory.
+    ConfigParse {
+        path: Option<PathBuf>,
+        source: serde_json::Error,
+    },
+    /// A file could not be opened or does not exist
+    MissingFile {
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// eSpeak-ng could not be initialized, usually because `espeak-ng-data` was not found
+    ESpeakInit(ESpeakError),
+    /// No voice with the given key is known
+    VoiceNotFound(String),
+    /// The requested speaker does not exist or cannot be used with this model
+    InvalidSpeaker(String),
+    /// onnxruntime failed to prepare the inputs of a model or to run it
+    Inference {
+        message: String,
+        source: ort::Error,
+    },
+    /// A model output does not have the expected shape or memory layout
+    TensorShape(String),
+    /// Post-processing or encoding the synthesized audio failed
+    AudioEncoding {
+        message: String,
+        source: Option<WaveWriterError>,
+    },
+    /// The synthesis was cancelled before it finished
+    Cancelled,
+    /// SSML input is malformed or uses an unsupported value
+    InvalidSsml(String),
+    /// The ONNX model does not match its config, or has inputs or outputs this crate cannot use
+    ModelMismatch(String),
+}
+
+/// A problem found while validating a model config
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigIssue {
+    /// `phoneme_id_map` has no ids for the pad, bos or eos symbol
+    MissingSpecialSymbol(char),
+    EmptyPhonemeIdMap,
+    /// The size of `speaker_id_map` does not agree with `num_speakers`
+    SpeakerCountMismatch {
+        num_speakers: u32,
+        speaker_id_map_len: usize,
+    },
+    SpeakerIdOutOfRange {
+        name: String,
+        id: i64,
+        num_speakers: u32,
+    },
+    ZeroSampleRate,
+    ZeroHopLength,
+    ScaleOutOfRange {
+        name: &'static str,
+        value: f32,
+    },
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigIssue::MissingSpecialSymbol(symbol) => {
+                write!(f, "phoneme_id_map has no id for `{}`", symbol)
+            }
+            ConfigIssue::EmptyPhonemeIdMap => write!(f, "phoneme_id_map is empty"),
+            ConfigIssue::SpeakerCountMismatch {
+                num_speakers,
+                speaker_id_map_len,
+            } => write!(
+                f,
+                "speaker_id_map has {} entries but num_speakers is {}",
+                speaker_id_map_len, num_speakers
+            ),
+            ConfigIssue::SpeakerIdOutOfRange {
+                name,
+                id,
+                num_speakers,
+            } => write!(
+                f,
+                "speaker `{}` has id {} which is out of range for {} speakers",
+                name, id, num_speakers
+            ),
+            ConfigIssue::ZeroSampleRate => write!(f, "audio.sample_rate is zero"),
+            ConfigIssue::ZeroHopLength => write!(f, "audio.hop_length is zero"),
+            ConfigIssue::ScaleOutOfRange { name, value } => {
+                write!(f, "inference.{} has an invalid value `{}`", name, value)
+            }
+        }
+    }
 }
 
 impl PiperError {
@@ -22,7 +126,21 @@ impl PiperError {
         Self::OperationError(message.into())
     }
 }
-impl Error for PiperError {}
+impl Error for PiperError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PiperError::ConfigParse { source, .. } => Some(source),
+            PiperError::MissingFile { source, .. } => Some(source),
+            PiperError::ESpeakInit(source) => Some(source),
+            PiperError::Inference { source, .. } => Some(source),
+            PiperError::AudioEncoding {
+                source: Some(source),
+                ..
+            } => Some(source),
+            _ => None,
+        }
+    }
+}
 
 impl fmt::Display for PiperError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -32,6 +150,49 @@ impl fmt::Display for PiperError {
             }
             PiperError::PhonemizationError(msg) => msg.to_string(),
             PiperError::OperationError(msg) => msg.to_string(),
+            PiperError::InvalidConfig(issues) => format!(
+                "Invalid model config: {}",
+                Vec::from_iter(issues.iter().map(|issue| issue.to_string())).join("; ")
+            ),
+            PiperError::ConfigParse {
+                path: Some(path),
+                source,
+            } => format!(
+                "Failed to parse `{}`. Caused by: `{}`",
+                path.display(),
+                source
+            ),
+            PiperError::ConfigParse { path: None, source } => {
+                format!(
+                    "Failed to parse config from memory. Caused by: `{}`",
+                    source
+                )
+            }
+            PiperError::MissingFile { path, source } => {
+                format!(
+                    "Failed to open `{}`. Caused by: `{}`",
+                    path.display(),
+                    source
+                )
+            }
+            PiperError::ESpeakInit(e) => e.to_string(),
+            PiperError::VoiceNotFound(key) => format!("No voice with key `{}` was found", key),
+            PiperError::InvalidSpeaker(msg) => msg.to_string(),
+            PiperError::Inference { message, source } => {
+                format!("{}. Error: {}", message, source)
+            }
+            PiperError::TensorShape(msg) => msg.to_string(),
+            PiperError::AudioEncoding {
+                message,
+                source: Some(source),
+            } => format!("{}. Error: {}", message, source),
+            PiperError::AudioEncoding {
+                message,
+                source: None,
+            } => message.to_string(),
+            PiperError::Cancelled => "The synthesis was cancelled".to_string(),
+            PiperError::InvalidSsml(msg) => format!("Invalid SSML: {}", msg),
+            PiperError::ModelMismatch(msg) => msg.to_string(),
         };
         write!(f, "{}", err_message)
     }
@@ -39,7 +200,10 @@ impl fmt::Display for PiperError {
 
 impl From<WaveWriterError> for PiperError {
     fn from(error: WaveWriterError) -> Self {
-        PiperError::OperationError(error.to_string())
+        PiperError::AudioEncoding {
+            message: "Failed to write wave samples".to_string(),
+            source: Some(error),
+        }
     }
 }
 
@@ -73,15 +237,122 @@ impl std::string::ToString for Phonemes {
     }
 }
 
+/// Selects a speaker of a multi-speaker model, either by id or by name,
+/// or conditions the decoder of a streaming model on a custom embedding.
+///
+/// A custom embedding only changes the voice timbre: the encoder still runs with
+/// speaker id 0, so phoneme durations and prosody are those of the first speaker.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Speaker {
+    Id(i64),
+    Name(String),
+    Embedding(SpeakerEmbedding),
+}
+
+impl From<i64> for Speaker {
+    fn from(sid: i64) -> Self {
+        Self::Id(sid)
+    }
+}
+
+impl From<&str> for Speaker {
+    fn from(name: &str) -> Self {
+        Self::Name(name.to_string())
+    }
+}
+
+impl From<String> for Speaker {
+    fn from(name: String) -> Self {
+        Self::Name(name)
+    }
+}
+
+impl From<SpeakerEmbedding> for Speaker {
+    fn from(embedding: SpeakerEmbedding) -> Self {
+        Self::Embedding(embedding)
+    }
+}
+
+/// Inference parameters used to synthesize speech with a VITS model
+#[derive(Debug, Clone, Default)]
+pub struct PiperSynthesisConfig {
+    pub speaker: Option<Speaker>,
+    pub noise_scale: f32,
+    pub length_scale: f32,
+    pub noise_w: f32,
+}
+
+/// Time taken by each step of [`PiperModel::warm_up`]
+#[derive(Debug, Clone, Default)]
+pub struct WarmUpTimings {
+    /// Initializing eSpeak and phonemizing a short text with the voice
+    pub phonemization: Duration,
+    /// Number of phonemes and synthesis time of each dummy sentence
+    pub sentences: Vec<(usize, Duration)>,
+    /// Decoder time of each chunk of a dummy stream, for streaming voices
+    pub decoder_chunks: Vec<Duration>,
+    pub total: Duration,
+}
+
+/// A text-to-speech model.
+///
+/// Synthesis configs are passed around as concrete [`PiperSynthesisConfig`] values rather than
+/// through an associated type, so the trait stays object safe and models can keep being shared
+/// as `Arc<dyn PiperModel + Send + Sync>`, which is what [`PiperSpeechSynthesizer`] consumes.
+///
+/// [`PiperSpeechSynthesizer`]: crate::synth::PiperSpeechSynthesizer
 pub trait PiperModel {
     fn audio_output_info(&self) -> PiperResult<AudioInfo>;
     fn phonemize_text(&self, text: &str) -> PiperResult<Phonemes>;
     fn speak_batch(&self, phoneme_batches: Vec<String>) -> PiperResult<Vec<Audio>>;
     fn speak_one_sentence(&self, phonemes: String) -> PiperAudioResult;
+    /// Like [`PiperModel::speak_batch`], but uses the given config instead of the
+    /// model's fallback config. The model itself is left untouched.
+    fn speak_batch_with_config(
+        &self,
+        #[allow(unused_variables)] phoneme_batches: Vec<String>,
+        #[allow(unused_variables)] synthesis_config: &PiperSynthesisConfig,
+    ) -> PiperResult<Vec<Audio>> {
+        Err(PiperError::OperationError(
+            "Synthesis with a given config is not supported for this model".to_string(),
+        ))
+    }
+    /// Like [`PiperModel::speak_one_sentence`], but uses the given config instead of the
+    /// model's fallback config. The model itself is left untouched.
+    fn speak_one_sentence_with_config(
+        &self,
+        #[allow(unused_variables)] phonemes: String,
+        #[allow(unused_variables)] synthesis_config: &PiperSynthesisConfig,
+    ) -> PiperAudioResult {
+        Err(PiperError::OperationError(
+            "Synthesis with a given config is not supported for this model".to_string(),
+        ))
+    }
 
-    fn get_default_synthesis_config(&self) -> PiperResult<Box<dyn Any>>;
-    fn get_fallback_synthesis_config(&self) -> PiperResult<Box<dyn Any>>;
-    fn set_fallback_synthesis_config(&self, synthesis_config: &dyn Any) -> PiperResult<()>;
+    fn get_default_synthesis_config(&self) -> PiperResult<PiperSynthesisConfig>;
+    /// The config used when none is given. Defaults to the default config,
+    /// for models whose fallback config cannot be changed.
+    fn get_fallback_synthesis_config(&self) -> PiperResult<PiperSynthesisConfig> {
+        self.get_default_synthesis_config()
+    }
+    fn set_fallback_synthesis_config(
+        &self,
+        synthesis_config: &PiperSynthesisConfig,
+    ) -> PiperResult<()>;
+    /// Sets the fallback config from a type-erased value, as the trait accepted before
+    /// synthesis configs became typed. Only [`PiperSynthesisConfig`] values are accepted.
+    #[deprecated(
+        since = "0.1.9",
+        note = "pass a `PiperSynthesisConfig` to `set_fallback_synthesis_config` instead"
+    )]
+    fn set_fallback_synthesis_config_any(&self, synthesis_config: &dyn Any) -> PiperResult<()> {
+        match synthesis_config.downcast_ref::<PiperSynthesisConfig>() {
+            Some(new_config) => self.set_fallback_synthesis_config(new_config),
+            None => Err(PiperError::OperationError(
+                "Invalid configuration for Vits Model".to_string(),
+            )),
+        }
+    }
 
     fn get_language(&self) -> PiperResult<Option<String>> {
         Ok(None)
@@ -109,6 +380,49 @@ pub trait PiperModel {
     fn properties(&self) -> PiperResult<HashMap<String, String>> {
         Ok(HashMap::with_capacity(0))
     }
+    /// Inputs, outputs and metadata of the onnxruntime sessions backing this model
+    fn get_input_output_info(&self) -> PiperResult<Vec<SessionInfo>> {
+        Err(PiperError::OperationError(
+            "Model introspection is not supported for this model".to_string(),
+        ))
+    }
+    /// Converts the phonemes of one sentence to model input ids,
+    /// reporting the phonemes that have no id
+    fn phonemes_to_ids(
+        &self,
+        #[allow(unused_variables)] phonemes: &str,
+    ) -> PiperResult<PhonemeIds> {
+        Err(PiperError::OperationError(
+            "Phoneme id mapping is not supported for this model".to_string(),
+        ))
+    }
+
+    /// The conditioning vector the model derives from the given speaker
+    fn speaker_embedding(
+        &self,
+        #[allow(unused_variables)] speaker: &Speaker,
+    ) -> PiperResult<SpeakerEmbedding> {
+        Err(PiperError::OperationError(
+            "Speaker embeddings are not supported for this model".to_string(),
+        ))
+    }
+    /// Blends the embeddings of several speakers using the given weights.
+    /// See [`Speaker::Embedding`] for what the blend does and does not change.
+    fn blend_speakers(&self, speakers: &[(Speaker, f32)]) -> PiperResult<SpeakerEmbedding> {
+        let mut weighted = Vec::with_capacity(speakers.len());
+        for (speaker, weight) in speakers {
+            weighted.push((self.speaker_embedding(speaker)?, *weight));
+        }
+        SpeakerEmbedding::blend(&weighted)
+    }
+
+    /// Initializes eSpeak and runs dummy syntheses, so that the first request
+    /// does not pay for the lazy initialization of eSpeak and onnxruntime
+    fn warm_up(&self) -> PiperResult<WarmUpTimings> {
+        Err(PiperError::OperationError(
+            "Warm up is not supported for this model".to_string(),
+        ))
+    }
 
     fn supports_streaming_output(&self) -> bool {
         false
@@ -118,9 +432,48 @@ pub trait PiperModel {
         #[allow(unused_variables)] phonemes: String,
         #[allow(unused_variables)] chunk_size: usize,
         #[allow(unused_variables)] chunk_padding: usize,
-    ) -> PiperResult<AudioStreamIterator> {
+    ) -> PiperResult<AudioStreamIterator<'_>> {
+        Err(PiperError::OperationError(
+            "Streaming synthesis is not supported for this model".to_string(),
+        ))
+    }
+    fn stream_synthesis_with_config(
+        &self,
+        #[allow(unused_variables)] phonemes: String,
+        #[allow(unused_variables)] synthesis_config: &PiperSynthesisConfig,
+        #[allow(unused_variables)] chunk_size: usize,
+        #[allow(unused_variables)] chunk_padding: usize,
+    ) -> PiperResult<AudioStreamIterator<'_>> {
         Err(PiperError::OperationError(
             "Streaming synthesis is not supported for this model".to_string(),
         ))
     }
+    /// Like [`PiperModel::stream_synthesis_with_config`], or [`PiperModel::stream_synthesis`]
+    /// if no config is given, but stops between chunks once `cancellation` is cancelled,
+    /// before running the decoder on the next one
+    fn stream_synthesis_cancellable(
+        &self,
+        phonemes: String,
+        synthesis_config: Option<&PiperSynthesisConfig>,
+        chunk_size: usize,
+        chunk_padding: usize,
+        cancellation: CancellationToken,
+    ) -> PiperResult<AudioStreamIterator<'_>> {
+        let mut stream = match synthesis_config {
+            Some(synthesis_config) => self.stream_synthesis_with_config(
+                phonemes,
+                synthesis_config,
+                chunk_size,
+                chunk_padding,
+            )?,
+            None => self.stream_synthesis(phonemes, chunk_size, chunk_padding)?,
+        };
+        Ok(Box::new(std::iter::from_fn(move || {
+            if cancellation.is_cancelled() {
+                None
+            } else {
+                stream.next()
+            }
+        })))
+    }
 }
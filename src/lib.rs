@@ -1,29 +1,63 @@
 use espeak_rs::text_to_phonemes;
-use ndarray::Axis;
-use ndarray::{Array, Array1, Array2, ArrayView, Dim, IxDynImpl};
-use ort::execution_providers;
+use half::f16;
+use ndarray::{Array, Array1, Array2, Array3, ArrayView, CowArray, Dim, IxDynImpl};
+use ndarray::{Axis, Slice};
+use ort::execution_providers::ExecutionProviderDispatch;
+use ort::session::builder::GraphOptimizationLevel;
 use ort::session::{Session, SessionInputValue, SessionInputs, SessionOutputs};
-use ort::value::Value;
+use ort::value::{DynValue, Value};
 use serde::Deserialize;
+use unicode_normalization::UnicodeNormalization;
 
 mod audio;
 mod core;
+pub mod model_info;
+pub mod normalize;
+pub mod phoneme_ids;
+pub mod registry;
+pub mod speaker_embedding;
+pub mod ssml;
 pub use audio::synth;
-use core::{Audio, AudioInfo, AudioSamples, AudioStreamIterator, Phonemes, PiperModel};
-pub use core::{PiperAudioResult, PiperError, PiperResult};
+use audio::synth::CancellationToken;
+use core::{Audio, AudioInfo, AudioSamples, AudioStreamIterator, Phonemes};
+pub use core::{
+    AudioChunk, ConfigIssue, PhonemeAlignment, PiperAudioResult, PiperError, PiperModel,
+    PiperResult, PiperSynthesisConfig, Speaker, SynthesisTimings, WarmUpTimings,
+};
+use model_info::{BatchOutputLengths, FloatType, FloatTypes, SessionInfo, DURATION_OUTPUT_NAMES};
+use normalize::{NormalizerOption, TextNormalizer};
+pub use ort::execution_providers;
+use phoneme_ids::{PhonemeIdConfig, PhonemeIds};
+use speaker_embedding::SpeakerEmbedding;
 
-use std::any::Any;
 use std::borrow::Cow;
 use std::collections::HashMap;
+use std::ffi::OsStr;
 use std::fs::File;
+use std::io::{self, Read};
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::{Duration, Instant};
 
-const MIN_CHUNK_SIZE: isize = 44;
+const MIN_CHUNK_SIZE: usize = 44;
 const MAX_CHUNK_SIZE: usize = 1024;
 const BOS: char = '^';
 const EOS: char = '$';
 const PAD: char = '_';
+/// Number of audio samples the decoder produces per mel frame, unless the config
+/// or the decoder say otherwise
+const HOP_LENGTH: usize = 256;
+/// Number of mel frames decoded at load time to measure the hop length of a decoder
+const HOP_LENGTH_PROBE_FRAMES: usize = 16;
+/// Length of the crossfade between clauses of non-streaming voices streamed clause by clause
+const CLAUSE_CROSSFADE_MS: usize = 10;
+/// Text phonemized by [`PiperModel::warm_up`] to initialize eSpeak
+const WARM_UP_TEXT: &str = "Warm up.";
+/// Number of phonemes of the dummy sentences synthesized by [`PiperModel::warm_up`]
+const WARM_UP_SENTENCE_LENGTHS: [usize; 3] = [8, 32, 128];
+/// Chunk size and padding, in mel frames, of the dummy stream of streaming voices
+const WARM_UP_CHUNK_SIZE: usize = MIN_CHUNK_SIZE;
+const WARM_UP_CHUNK_PADDING: usize = 3;
 
 #[inline(always)]
 fn reversed_mapping<K, V>(input: &HashMap<K, V>) -> HashMap<V, K>
@@ -38,23 +72,45 @@ fn load_model_config(config_path: &Path) -> PiperResult<(ModelConfig, PiperSynth
     let file = match File::open(config_path) {
         Ok(file) => file,
         Err(why) => {
-            return Err(PiperError::FailedToLoadResource(format!(
-                "Faild to load model config: `{}`. Caused by: `{}`",
-                config_path.display(),
-                why
-            )))
+            return Err(PiperError::MissingFile {
+                path: config_path.to_path_buf(),
+                source: why,
+            })
         }
     };
-    let model_config: ModelConfig = match serde_json::from_reader(file) {
-        Ok(config) => config,
+    let configs = match parse_model_config(file) {
+        Ok(configs) => configs,
         Err(why) => {
-            return Err(PiperError::FailedToLoadResource(format!(
-                "Faild to parse model config from file: `{}`. Caused by: `{}`",
-                config_path.display(),
-                why
-            )))
+            return Err(PiperError::ConfigParse {
+                path: Some(config_path.to_path_buf()),
+                source: why,
+            })
         }
     };
+    validate_model_config(&configs.0)?;
+    Ok(configs)
+}
+
+fn load_model_config_from_bytes(
+    config_json: &[u8],
+) -> PiperResult<(ModelConfig, PiperSynthesisConfig)> {
+    let configs = match parse_model_config(config_json) {
+        Ok(configs) => configs,
+        Err(why) => {
+            return Err(PiperError::ConfigParse {
+                path: None,
+                source: why,
+            })
+        }
+    };
+    validate_model_config(&configs.0)?;
+    Ok(configs)
+}
+
+fn parse_model_config(
+    reader: impl Read,
+) -> Result<(ModelConfig, PiperSynthesisConfig), serde_json::Error> {
+    let model_config: ModelConfig = serde_json::from_reader(reader)?;
     let synth_config = PiperSynthesisConfig {
         speaker: None,
         noise_scale: model_config.inference.noise_scale,
@@ -64,45 +120,539 @@ fn load_model_config(config_path: &Path) -> PiperResult<(ModelConfig, PiperSynth
     Ok((model_config, synth_config))
 }
 
-fn create_inference_session(model_path: &Path) -> Result<Session, ort::Error> {
-    let exec_provider = execution_providers::CPUExecutionProvider::default().build();
-    Session::builder()?
-        // .with_parallel_execution(true)?
-        // .with_inter_threads(16)?
-        // .with_optimization_level(ort::GraphOptimizationLevel::Level3)?
-        .with_memory_pattern(false)?
-        .with_execution_providers([exec_provider])?
-        .commit_from_file(model_path)
+/// Splits text into sentences of lowercased, NFD-decomposed characters,
+/// the phonemes used by voices trained on raw text.
+fn text_to_codepoints(text: &str) -> Vec<String> {
+    let mut sentences = Vec::new();
+    let mut sentence = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        sentence.push(c);
+        let at_boundary =
+            matches!(c, '.' | '!' | '?') && chars.peek().is_none_or(|next| next.is_whitespace());
+        if at_boundary {
+            sentences.push(std::mem::take(&mut sentence));
+        }
+    }
+    sentences.push(sentence);
+    Vec::from_iter(
+        sentences
+            .iter()
+            .map(|sentence| sentence.trim())
+            .filter(|sentence| !sentence.is_empty())
+            .map(|sentence| sentence.to_lowercase().nfd().collect::<String>()),
+    )
+}
+
+/// Splits the phonemes of one sentence after commas and other clause breaks
+/// that are followed by whitespace. The breaks stay with their clause.
+fn split_clauses(phonemes: &str) -> Vec<&str> {
+    let mut clauses = Vec::new();
+    let mut start = 0;
+    let mut chars = phonemes.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        let at_break = matches!(
+            c,
+            ',' | ';' | ':' | '—' | '–' | '…' | '，' | '；' | '：' | '、'
+        ) && chars.peek().is_none_or(|(_, next)| next.is_whitespace());
+        if at_break {
+            let end = index + c.len_utf8();
+            clauses.push(&phonemes[start..end]);
+            start = end;
+        }
+    }
+    clauses.push(&phonemes[start..]);
+    let clauses = Vec::from_iter(
+        clauses
+            .into_iter()
+            .map(|clause| clause.trim())
+            .filter(|clause| !clause.is_empty()),
+    );
+    if clauses.is_empty() {
+        vec![phonemes]
+    } else {
+        clauses
+    }
+}
+
+/// Checks the parsed config for problems that would otherwise surface during synthesis
+fn validate_model_config(config: &ModelConfig) -> PiperResult<()> {
+    let mut issues = Vec::new();
+    if config.phoneme_id_map.is_empty() {
+        issues.push(ConfigIssue::EmptyPhonemeIdMap);
+    }
+    for symbol in [PAD, BOS, EOS] {
+        match config.phoneme_id_map.get(&symbol) {
+            Some(ids) if !ids.is_empty() => {}
+            _ => issues.push(ConfigIssue::MissingSpecialSymbol(symbol)),
+        }
+    }
+    let num_speakers = config.num_speakers;
+    let speaker_id_map_len = config.speaker_id_map.len();
+    if (num_speakers > 1 && speaker_id_map_len != num_speakers as usize)
+        || (num_speakers <= 1 && speaker_id_map_len > 1)
+    {
+        issues.push(ConfigIssue::SpeakerCountMismatch {
+            num_speakers,
+            speaker_id_map_len,
+        });
+    }
+    if num_speakers > 1 {
+        let mut speakers = Vec::from_iter(config.speaker_id_map.iter());
+        speakers.sort_by_key(|(_, id)| **id);
+        for (name, id) in speakers {
+            if *id < 0 || *id >= num_speakers as i64 {
+                issues.push(ConfigIssue::SpeakerIdOutOfRange {
+                    name: name.clone(),
+                    id: *id,
+                    num_speakers,
+                });
+            }
+        }
+    }
+    if config.audio.sample_rate == 0 {
+        issues.push(ConfigIssue::ZeroSampleRate);
+    }
+    if config.audio.hop_length == Some(0) {
+        issues.push(ConfigIssue::ZeroHopLength);
+    }
+    let inference = &config.inference;
+    for (name, value, allow_zero) in [
+        ("noise_scale", inference.noise_scale, true),
+        ("length_scale", inference.length_scale, false),
+        ("noise_w", inference.noise_w, true),
+    ] {
+        if !value.is_finite() || value < 0.0 || (value == 0.0 && !allow_zero) {
+            issues.push(ConfigIssue::ScaleOutOfRange { name, value });
+        }
+    }
+    if issues.is_empty() {
+        Ok(())
+    } else {
+        Err(PiperError::InvalidConfig(issues))
+    }
+}
+
+/// Where the ONNX graph of a model is read from
+#[derive(Clone, Copy)]
+enum ModelSource<'a> {
+    File(&'a Path),
+    Memory(&'a [u8]),
+}
+
+/// Graph optimization level applied by onnxruntime when a model is loaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptimizationLevel {
+    Disable,
+    Basic,
+    Extended,
+    All,
+}
+
+impl From<OptimizationLevel> for GraphOptimizationLevel {
+    fn from(level: OptimizationLevel) -> Self {
+        match level {
+            OptimizationLevel::Disable => GraphOptimizationLevel::Disable,
+            OptimizationLevel::Basic => GraphOptimizationLevel::Level1,
+            OptimizationLevel::Extended => GraphOptimizationLevel::Level2,
+            OptimizationLevel::All => GraphOptimizationLevel::Level3,
+        }
+    }
+}
+
+/// onnxruntime session options used when loading a model.
+///
+/// The defaults match what piper-rs has always used: a single CPU execution provider
+/// without memory pattern optimization, leaving thread counts and graph optimization
+/// to onnxruntime.
+#[derive(Debug, Clone, Default)]
+pub struct ModelOptions {
+    /// Number of threads used to parallelize execution within nodes
+    pub intra_threads: Option<usize>,
+    /// Number of threads used to run independent nodes in parallel.
+    /// Setting this enables onnxruntime's parallel execution mode.
+    pub inter_threads: Option<usize>,
+    pub optimization_level: Option<OptimizationLevel>,
+    pub memory_pattern: bool,
+    /// Use an arena allocator for the CPU execution provider
+    pub cpu_arena: bool,
+    /// Execution providers to register, in order of preference.
+    /// The CPU execution provider is always registered last as a fallback.
+    pub execution_providers: Vec<ExecutionProviderDispatch>,
+    /// Save the optimized graph to this path. For streaming models the encoder and decoder
+    /// graphs are saved next to it, with `encoder.` and `decoder.` prepended to the file name.
+    pub optimized_model_path: Option<PathBuf>,
+}
+
+/// Settings of a loaded voice that are not passed to onnxruntime.
+/// Set them with [`PiperModelBuilder::voice_options`].
+#[derive(Debug, Clone, Default)]
+pub struct VoiceOptions {
+    /// How phonemes are converted to model input ids
+    pub phoneme_ids: PhonemeIdConfig,
+    /// Maximum number of sentences passed to one inference run by `speak_batch`.
+    /// Defaults to 1. Models whose graph has a fixed batch dimension, or that export none of
+    /// `output_lengths`, `y_mask` or a phoneme duration output to trim their padded audio with,
+    /// always run one sentence at a time and log a warning if a larger size is given.
+    pub batch_size: Option<usize>,
+    /// How streaming models split mel frames into decoder chunks
+    pub mel_chunker: MelChunkerConfig,
+    /// Normalizer run on text before it is phonemized, defaults to the built-in one of
+    /// the voice's language
+    pub text_normalizer: NormalizerOption,
+}
+
+/// Limits of the chunks a streaming model decodes at a time, in mel frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MelChunkerConfig {
+    /// Frames left at the end of a sentence are decoded with the previous chunk
+    /// instead of on their own if there are this many or fewer
+    pub min_chunk_size: usize,
+    /// Chunks grow with every step, up to this size
+    pub max_chunk_size: usize,
+}
+
+impl Default for MelChunkerConfig {
+    fn default() -> Self {
+        Self {
+            min_chunk_size: MIN_CHUNK_SIZE,
+            max_chunk_size: MAX_CHUNK_SIZE,
+        }
+    }
+}
+
+impl MelChunkerConfig {
+    /// Rejects limits the chunker cannot make progress with
+    fn validate(&self) -> PiperResult<()> {
+        if self.max_chunk_size == 0 {
+            Err(PiperError::OperationError(
+                "The maximum mel chunk size must be at least one frame".to_string(),
+            ))
+        } else if self.min_chunk_size > self.max_chunk_size {
+            Err(PiperError::OperationError(format!(
+                "The minimum mel chunk size ({}) is larger than the maximum ({})",
+                self.min_chunk_size, self.max_chunk_size
+            )))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl ModelOptions {
+    fn optimized_model_path_for(&self, component: Option<&str>) -> Option<PathBuf> {
+        let path = self.optimized_model_path.as_ref()?;
+        match (component, path.file_name()) {
+            (Some(component), Some(filename)) => {
+                Some(path.with_file_name(format!("{}.{}", component, filename.to_string_lossy())))
+            }
+            _ => Some(path.clone()),
+        }
+    }
+}
+
+fn create_inference_session(
+    source: ModelSource,
+    options: &ModelOptions,
+    component: Option<&str>,
+) -> Result<Session, ort::Error> {
+    let mut cpu_provider = execution_providers::CPUExecutionProvider::default();
+    if options.cpu_arena {
+        cpu_provider = cpu_provider.with_arena_allocator();
+    }
+    let exec_providers = options
+        .execution_providers
+        .iter()
+        .cloned()
+        .chain([cpu_provider.build()]);
+    let mut builder = Session::builder()?
+        .with_memory_pattern(options.memory_pattern)?
+        .with_execution_providers(exec_providers)?;
+    if let Some(num_threads) = options.intra_threads {
+        builder = builder.with_intra_threads(num_threads)?;
+    }
+    if let Some(num_threads) = options.inter_threads {
+        builder = builder
+            .with_parallel_execution(true)?
+            .with_inter_threads(num_threads)?;
+    }
+    if let Some(level) = options.optimization_level {
+        builder = builder.with_optimization_level(level.into())?;
+    }
+    if let Some(path) = options.optimized_model_path_for(component) {
+        builder = builder.with_optimized_model_path(path)?;
+    }
+    match source {
+        ModelSource::File(model_path) => builder.commit_from_file(model_path),
+        ModelSource::Memory(model_bytes) => builder.commit_from_memory(model_bytes),
+    }
 }
 
 pub fn from_config_path(config_path: &Path) -> PiperResult<Arc<dyn PiperModel + Send + Sync>> {
-    let (config, synth_config) = load_model_config(config_path)?;
-    if config.streaming.unwrap_or_default() {
-        Ok(Arc::new(VitsStreamingModel::from_config(
+    from_config_path_with_options(config_path, &ModelOptions::default())
+}
+
+pub fn from_config_path_with_options(
+    config_path: &Path,
+    options: &ModelOptions,
+) -> PiperResult<Arc<dyn PiperModel + Send + Sync>> {
+    PiperModelBuilder::new(config_path)
+        .options(options.clone())
+        .build()
+}
+
+/// Loads a model from explicitly given files, or from their contents already in memory.
+///
+/// Whether a streaming or a non-streaming model is loaded is decided by the files given:
+/// a single ONNX model loads a [`VitsModel`], an encoder/decoder pair loads a
+/// [`VitsStreamingModel`]. If no model files are given, the `streaming` flag of the config
+/// decides and the files are looked up next to the config using piper's naming conventions
+/// (`<name>.onnx` for `<name>.onnx.json`, or `encoder.onnx` and `decoder.onnx`).
+pub struct PiperModelBuilder<'a> {
+    config: BuilderSource<'a>,
+    onnx: Option<BuilderSource<'a>>,
+    encoder: Option<BuilderSource<'a>>,
+    decoder: Option<BuilderSource<'a>>,
+    options: ModelOptions,
+    voice_options: VoiceOptions,
+}
+
+/// A file given to [`PiperModelBuilder`], or its contents
+enum BuilderSource<'a> {
+    Path(PathBuf),
+    Bytes(&'a [u8]),
+}
+
+impl BuilderSource<'_> {
+    fn model_source(&self, kind: &str) -> PiperResult<ModelSource<'_>> {
+        match self {
+            Self::Path(path) => {
+                ensure_model_file_exists(path, kind)?;
+                Ok(ModelSource::File(path))
+            }
+            Self::Bytes(bytes) => Ok(ModelSource::Memory(bytes)),
+        }
+    }
+}
+
+impl<'a> PiperModelBuilder<'a> {
+    pub fn new(config_path: impl Into<PathBuf>) -> Self {
+        Self::with_config(BuilderSource::Path(config_path.into()))
+    }
+    /// Loads the config from memory. The model files must then be given explicitly.
+    pub fn from_config_bytes(config_json: &'a [u8]) -> Self {
+        Self::with_config(BuilderSource::Bytes(config_json))
+    }
+    fn with_config(config: BuilderSource<'a>) -> Self {
+        Self {
             config,
-            synth_config,
-            &config_path.with_file_name("encoder.onnx"),
-            &config_path.with_file_name("decoder.onnx"),
-        )?))
-    } else {
-        let Some(onnx_filename) = config_path.file_stem() else {
-            return Err(PiperError::OperationError(format!(
+            onnx: None,
+            encoder: None,
+            decoder: None,
+            options: ModelOptions::default(),
+            voice_options: VoiceOptions::default(),
+        }
+    }
+    pub fn onnx_path(mut self, onnx_path: impl Into<PathBuf>) -> Self {
+        self.onnx = Some(BuilderSource::Path(onnx_path.into()));
+        self
+    }
+    pub fn onnx_bytes(mut self, onnx_bytes: &'a [u8]) -> Self {
+        self.onnx = Some(BuilderSource::Bytes(onnx_bytes));
+        self
+    }
+    pub fn encoder_path(mut self, encoder_path: impl Into<PathBuf>) -> Self {
+        self.encoder = Some(BuilderSource::Path(encoder_path.into()));
+        self
+    }
+    pub fn encoder_bytes(mut self, encoder_bytes: &'a [u8]) -> Self {
+        self.encoder = Some(BuilderSource::Bytes(encoder_bytes));
+        self
+    }
+    pub fn decoder_path(mut self, decoder_path: impl Into<PathBuf>) -> Self {
+        self.decoder = Some(BuilderSource::Path(decoder_path.into()));
+        self
+    }
+    pub fn decoder_bytes(mut self, decoder_bytes: &'a [u8]) -> Self {
+        self.decoder = Some(BuilderSource::Bytes(decoder_bytes));
+        self
+    }
+    /// onnxruntime session options
+    pub fn options(mut self, options: ModelOptions) -> Self {
+        self.options = options;
+        self
+    }
+    pub fn voice_options(mut self, voice_options: VoiceOptions) -> Self {
+        self.voice_options = voice_options;
+        self
+    }
+
+    pub fn build(self) -> PiperResult<Arc<dyn PiperModel + Send + Sync>> {
+        let (config, synth_config) = match &self.config {
+            BuilderSource::Path(config_path) => load_model_config(config_path)?,
+            BuilderSource::Bytes(config_json) => load_model_config_from_bytes(config_json)?,
+        };
+        let config_name = match &self.config {
+            BuilderSource::Path(config_path) => format!("`{}`", config_path.display()),
+            BuilderSource::Bytes(_) => "given".to_string(),
+        };
+        let config_streaming = config.streaming.unwrap_or_default();
+        let streaming = match (&self.onnx, &self.encoder, &self.decoder) {
+            (Some(_), None, None) => false,
+            (None, Some(_), Some(_)) => true,
+            (None, None, None) => config_streaming,
+            (Some(_), _, _) => {
+                return Err(PiperError::OperationError(
+                    "Both a single ONNX model and an encoder/decoder pair were given. Use either one of them".to_string(),
+                ))
+            }
+            (None, Some(_), None) => {
+                return Err(PiperError::OperationError(
+                    "An encoder was given without a decoder. Streaming models need both".to_string(),
+                ))
+            }
+            (None, None, Some(_)) => {
+                return Err(PiperError::OperationError(
+                    "A decoder was given without an encoder. Streaming models need both".to_string(),
+                ))
+            }
+        };
+        if streaming != config_streaming {
+            return Err(PiperError::ModelMismatch(if config_streaming {
+                format!(
+                    "The {} config declares a streaming model (`streaming: true`), but a single ONNX model was given instead of an encoder/decoder pair",
+                    config_name
+                )
+            } else {
+                format!(
+                    "The {} config declares a non-streaming model, but an encoder/decoder pair was given instead of a single ONNX model",
+                    config_name
+                )
+            }));
+        }
+
+        // Files that were not given are looked up next to the config
+        let next_to_config = |file_name: Option<&OsStr>| match (&self.config, file_name) {
+            (BuilderSource::Path(config_path), Some(file_name)) => {
+                Ok(BuilderSource::Path(config_path.with_file_name(file_name)))
+            }
+            (BuilderSource::Path(config_path), None) => Err(PiperError::OperationError(format!(
                 "Invalid config filename format `{}`",
                 config_path.display()
-            )));
+            ))),
+            (BuilderSource::Bytes(_), _) => Err(PiperError::OperationError(
+                "The model files of a config loaded from memory must be given explicitly"
+                    .to_string(),
+            )),
         };
-        Ok(Arc::new(VitsModel::from_config(
-            config,
-            synth_config,
-            &config_path.with_file_name(onnx_filename),
-        )?))
+        if streaming {
+            let encoder = match self.encoder {
+                Some(encoder) => encoder,
+                None => next_to_config(Some(OsStr::new("encoder.onnx")))?,
+            };
+            let decoder = match self.decoder {
+                Some(decoder) => decoder,
+                None => next_to_config(Some(OsStr::new("decoder.onnx")))?,
+            };
+            Ok(Arc::new(VitsStreamingModel::from_config(
+                config,
+                synth_config,
+                encoder.model_source("encoder")?,
+                decoder.model_source("decoder")?,
+                &self.options,
+                &self.voice_options,
+            )?))
+        } else {
+            let onnx = match self.onnx {
+                Some(onnx) => onnx,
+                None => match &self.config {
+                    BuilderSource::Path(config_path) => next_to_config(config_path.file_stem())?,
+                    BuilderSource::Bytes(_) => next_to_config(None)?,
+                },
+            };
+            Ok(Arc::new(VitsModel::from_config(
+                config,
+                synth_config,
+                onnx.model_source("ONNX model")?,
+                &self.options,
+                &self.voice_options,
+            )?))
+        }
+    }
+}
+
+fn ensure_model_file_exists(path: &Path, kind: &str) -> PiperResult<()> {
+    if path.is_file() {
+        Ok(())
+    } else {
+        Err(PiperError::MissingFile {
+            path: path.to_path_buf(),
+            source: io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("The {} file does not exist", kind),
+            ),
+        })
     }
 }
 
+/// Load a non-streaming voice from an in-memory JSON config and ONNX model.
+///
+/// Use [`from_streaming_config_bytes`] for voices exported as an encoder/decoder pair.
+pub fn from_config_bytes(
+    config_json: &[u8],
+    onnx_bytes: &[u8],
+) -> PiperResult<Arc<dyn PiperModel + Send + Sync>> {
+    from_config_bytes_with_options(config_json, onnx_bytes, &ModelOptions::default())
+}
+
+pub fn from_config_bytes_with_options(
+    config_json: &[u8],
+    onnx_bytes: &[u8],
+    options: &ModelOptions,
+) -> PiperResult<Arc<dyn PiperModel + Send + Sync>> {
+    Ok(Arc::new(VitsModel::from_bytes_with_options(
+        config_json,
+        onnx_bytes,
+        options,
+    )?))
+}
+
+/// Load a streaming voice from an in-memory JSON config and encoder/decoder ONNX models.
+pub fn from_streaming_config_bytes(
+    config_json: &[u8],
+    encoder_bytes: &[u8],
+    decoder_bytes: &[u8],
+) -> PiperResult<Arc<dyn PiperModel + Send + Sync>> {
+    from_streaming_config_bytes_with_options(
+        config_json,
+        encoder_bytes,
+        decoder_bytes,
+        &ModelOptions::default(),
+    )
+}
+
+pub fn from_streaming_config_bytes_with_options(
+    config_json: &[u8],
+    encoder_bytes: &[u8],
+    decoder_bytes: &[u8],
+    options: &ModelOptions,
+) -> PiperResult<Arc<dyn PiperModel + Send + Sync>> {
+    Ok(Arc::new(VitsStreamingModel::from_bytes_with_options(
+        config_json,
+        encoder_bytes,
+        decoder_bytes,
+        options,
+    )?))
+}
+
 #[derive(Deserialize, Default)]
 pub struct AudioConfig {
     pub sample_rate: u32,
     pub quality: Option<String>,
+    /// Audio samples per mel frame. If missing, streaming models measure it from their
+    /// decoder when loaded and other models assume 256.
+    #[serde(default)]
+    pub hop_length: Option<usize>,
 }
 
 #[derive(Deserialize, Default)]
@@ -110,6 +660,17 @@ pub struct ESpeakConfig {
     voice: String,
 }
 
+/// The front end used to turn text into phonemes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PhonemeType {
+    /// IPA phonemes produced by espeak-ng
+    #[default]
+    ESpeak,
+    /// The (lowercased, NFD-decomposed) characters of the text itself
+    Text,
+}
+
 #[derive(Deserialize, Default, Clone)]
 pub struct InferenceConfig {
     noise_scale: f32,
@@ -138,53 +699,101 @@ pub struct ModelConfig {
     pub num_speakers: u32,
     pub speaker_id_map: HashMap<String, i64>,
     streaming: Option<bool>,
+    #[serde(default)]
+    pub phoneme_type: PhonemeType,
+    #[serde(default)]
     espeak: ESpeakConfig,
     inference: InferenceConfig,
-    #[allow(dead_code)]
     num_symbols: u32,
-    #[allow(dead_code)]
-    phoneme_map: HashMap<i64, char>,
+    #[serde(default)]
+    phoneme_map: HashMap<char, Vec<char>>,
     phoneme_id_map: HashMap<char, Vec<i64>>,
 }
 
-#[derive(Debug, Clone, Default)]
-pub struct PiperSynthesisConfig {
-    pub speaker: Option<i64>,
-    pub noise_scale: f32,
-    pub length_scale: f32,
-    pub noise_w: f32,
-}
-
 trait VitsModelCommons {
     fn get_synth_config(&self) -> &RwLock<PiperSynthesisConfig>;
     fn get_config(&self) -> &ModelConfig;
     fn get_speaker_map(&self) -> &HashMap<i64, String>;
-    fn get_meta_ids(&self) -> (i64, i64, i64) {
-        let config = self.get_config();
-        let pad_id = *config.phoneme_id_map.get(&PAD).unwrap().first().unwrap();
-        let bos_id = *config.phoneme_id_map.get(&BOS).unwrap().first().unwrap();
-        let eos_id = *config.phoneme_id_map.get(&EOS).unwrap().first().unwrap();
-        (pad_id, bos_id, eos_id)
+    fn get_phoneme_id_config(&self) -> &PhonemeIdConfig;
+    fn get_text_normalizer(&self) -> Option<&dyn TextNormalizer>;
+    fn hop_length(&self) -> usize;
+    /// The synth config stays consistent even if a holder of the lock panicked,
+    /// so a poisoned lock is recovered instead of failing every later request
+    fn read_synth_config(&self) -> RwLockReadGuard<'_, PiperSynthesisConfig> {
+        match self.get_synth_config().read() {
+            Ok(synth_config) => synth_config,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+    fn write_synth_config(&self) -> RwLockWriteGuard<'_, PiperSynthesisConfig> {
+        match self.get_synth_config().write() {
+            Ok(synth_config) => synth_config,
+            Err(poisoned) => poisoned.into_inner(),
+        }
     }
     fn set_speaker(&self, sid: i64) -> Option<PiperError> {
-        let mut synth_config = self.get_synth_config().write().unwrap();
+        let mut synth_config = self.write_synth_config();
 
         if self.get_speaker_map().contains_key(&sid) {
-            synth_config.speaker = Some(sid);
+            synth_config.speaker = Some(Speaker::Id(sid));
             None // No error
         } else {
-            Some(PiperError::OperationError(format!(
+            Some(PiperError::InvalidSpeaker(format!(
                 "Invalid speaker id `{}`",
                 sid
             )))
         }
     }
+    /// Resolves the speaker of the given config to the id passed to the model.
+    /// Single-speaker models ignore the speaker, and get speaker 0 if they take an id anyway.
+    fn resolve_speaker_id(&self, synth_config: &PiperSynthesisConfig) -> PiperResult<Option<i64>> {
+        let multi_speaker = self.get_config().num_speakers > 1;
+        match synth_config.speaker {
+            // The encoder still needs a speaker id, its embedding is replaced afterwards.
+            // Durations and prosody therefore stay those of speaker 0.
+            Some(Speaker::Embedding(_)) => {
+                if self.supports_speaker_embeddings() {
+                    Ok(Some(0))
+                } else {
+                    Err(PiperError::InvalidSpeaker(
+                        "Custom speaker embeddings need a multi-speaker streaming model"
+                            .to_string(),
+                    ))
+                }
+            }
+            _ if !multi_speaker => Ok(self.has_speaker_input().then_some(0)),
+            None => Ok(Some(0)),
+            Some(Speaker::Id(sid)) => {
+                if self.get_speaker_map().contains_key(&sid) {
+                    Ok(Some(sid))
+                } else {
+                    Err(PiperError::InvalidSpeaker(format!(
+                        "No speaker was found with the given id `{}`",
+                        sid
+                    )))
+                }
+            }
+            Some(Speaker::Name(ref name)) => match self.get_config().speaker_id_map.get(name) {
+                Some(sid) => Ok(Some(*sid)),
+                None => Err(PiperError::InvalidSpeaker(format!(
+                    "No speaker was found with the given name `{}`",
+                    name
+                ))),
+            },
+        }
+    }
+    fn supports_speaker_embeddings(&self) -> bool {
+        false
+    }
+    /// Whether the model takes a speaker id, even if its config declares a single speaker
+    fn has_speaker_input(&self) -> bool {
+        self.get_config().num_speakers > 1
+    }
+    fn fallback_synth_config(&self) -> PiperSynthesisConfig {
+        self.read_synth_config().clone()
+    }
     fn language(&self) -> Option<String> {
-        self.get_config()
-            .language
-            .as_ref()
-            .map(|lang| lang.code.clone())
-            .or_else(|| Some(self.get_config().espeak.voice.clone()))
+        Some(config_language(self.get_config()))
     }
     fn get_properties(&self) -> HashMap<String, String> {
         HashMap::from([(
@@ -198,46 +807,74 @@ trait VitsModelCommons {
     }
 
     fn _do_set_default_synth_config(&self, new_config: &PiperSynthesisConfig) -> PiperResult<()> {
-        let mut synth_config = self.get_synth_config().write().unwrap();
+        let speaker = match new_config.speaker {
+            Some(Speaker::Embedding(_)) => {
+                self.resolve_speaker_id(new_config)?;
+                new_config.speaker.clone()
+            }
+            Some(_) => self.resolve_speaker_id(new_config)?.map(Speaker::Id),
+            None => None,
+        };
+        let mut synth_config = self.write_synth_config();
         synth_config.length_scale = new_config.length_scale;
         synth_config.noise_scale = new_config.noise_scale;
         synth_config.noise_w = new_config.noise_w;
-        if let Some(sid) = new_config.speaker {
-            if self.get_speaker_map().contains_key(&sid) {
-                synth_config.speaker = Some(sid);
-            } else {
-                return Err(PiperError::OperationError(format!(
-                    "No speaker was found with the given id `{}`",
-                    sid
-                )));
-            }
+        if let Some(speaker) = speaker {
+            synth_config.speaker = Some(speaker);
         }
         Ok(())
     }
-    fn phonemes_to_input_ids(
-        &self,
-        phonemes: &str,
-        pad_id: i64,
-        bos_id: i64,
-        eos_id: i64,
-    ) -> Vec<i64> {
+    fn phonemes_to_input_ids(&self, phonemes: &str) -> PiperResult<PhonemeIds> {
+        let _span = tracing::trace_span!("phoneme_ids", num_chars = phonemes.len()).entered();
         let config = self.get_config();
-        let mut phoneme_ids: Vec<i64> = Vec::with_capacity((phonemes.len() + 1) * 2);
-        phoneme_ids.push(bos_id);
-        // append padding in front to behave the same way piper-phonemize does
-        phoneme_ids.push(pad_id);
-        for phoneme in phonemes.chars() {
-            if let Some(id) = config.phoneme_id_map.get(&phoneme) {
-                phoneme_ids.push(*id.first().unwrap());
-                phoneme_ids.push(pad_id);
-            }
+        let phoneme_ids = phoneme_ids::phonemes_to_ids(
+            phonemes,
+            &config.phoneme_id_map,
+            &config.phoneme_map,
+            self.get_phoneme_id_config(),
+        )?;
+        if !phoneme_ids.missing.is_empty() {
+            tracing::warn!(
+                missing = ?phoneme_ids.missing,
+                phonemes,
+                "dropped phonemes that are not in the phoneme id map"
+            );
         }
-        phoneme_ids.push(eos_id);
-        phoneme_ids
+        Ok(phoneme_ids)
+    }
+    /// A dummy sentence of `num_phonemes` phonemes that all have an id
+    fn warm_up_phonemes(&self, num_phonemes: usize) -> String {
+        let id_config = self.get_phoneme_id_config();
+        let special = [id_config.pad, id_config.bos, id_config.eos];
+        let mut symbols = Vec::from_iter(
+            self.get_config()
+                .phoneme_id_map
+                .keys()
+                .copied()
+                .filter(|symbol| !special.contains(symbol) && !symbol.is_whitespace()),
+        );
+        symbols.sort_unstable();
+        symbols.into_iter().cycle().take(num_phonemes).collect()
+    }
+    fn timed_input_ids(&self, phonemes: &str) -> PiperResult<(Vec<i64>, Duration)> {
+        let timer = Instant::now();
+        let phoneme_ids = self.phonemes_to_input_ids(phonemes)?.ids;
+        Ok((phoneme_ids, timer.elapsed()))
     }
     fn do_phonemize_text(&self, text: &str) -> PiperResult<Phonemes> {
         let config = self.get_config();
-        let text = Cow::from(text);
+        let text = match self.get_text_normalizer() {
+            Some(normalizer) => {
+                let _span = tracing::trace_span!("normalize", num_chars = text.len()).entered();
+                Cow::Owned(normalizer.normalize(text))
+            }
+            None => Cow::Borrowed(text),
+        };
+        if config.phoneme_type == PhonemeType::Text {
+            return Ok(text_to_codepoints(&text).into());
+        }
+        let _span = tracing::debug_span!("phonemize", voice = %config.espeak.voice).entered();
+        espeak_rs::initialize().map_err(PiperError::ESpeakInit)?;
         let phonemes = match text_to_phonemes(&text, &config.espeak.voice, None, true, false) {
             Ok(ph) => ph,
             Err(e) => {
@@ -250,6 +887,42 @@ trait VitsModelCommons {
         Ok(phonemes.into())
     }
 
+    /// Converts the duration of each phoneme id, in mel frames, to start and end times.
+    /// Durations past the end of `phoneme_ids` (batch padding) are ignored.
+    fn phoneme_alignment(
+        &self,
+        phoneme_ids: &[i64],
+        durations: ArrayView<f32, Dim<IxDynImpl>>,
+    ) -> Vec<PhonemeAlignment> {
+        let config = self.get_config();
+        let id_to_phoneme: HashMap<i64, char> = config
+            .phoneme_id_map
+            .iter()
+            .filter_map(|(phoneme, ids)| match ids.as_slice() {
+                [id] => Some((*id, *phoneme)),
+                _ => None,
+            })
+            .collect();
+        let ms_per_frame = self.hop_length() as f32 * 1000.0 / config.audio.sample_rate as f32;
+        let mut start_frame = 0.0f32;
+        Vec::from_iter(
+            phoneme_ids
+                .iter()
+                .zip(durations.iter())
+                .map(|(phoneme_id, duration)| {
+                    let end_frame = start_frame + duration.max(0.0);
+                    let alignment = PhonemeAlignment {
+                        phoneme_id: *phoneme_id,
+                        phoneme: id_to_phoneme.get(phoneme_id).copied(),
+                        start_ms: start_frame * ms_per_frame,
+                        end_ms: end_frame * ms_per_frame,
+                    };
+                    start_frame = end_frame;
+                    alignment
+                }),
+        )
+    }
+
     fn get_audio_output_info(&self) -> PiperResult<AudioInfo> {
         Ok(AudioInfo {
             sample_rate: self.get_config().audio.sample_rate as usize,
@@ -259,110 +932,399 @@ trait VitsModelCommons {
     }
 }
 
+/// Builds the inputs of a VITS model or streaming encoder for a batch of phoneme id sequences.
+/// Shorter sequences are zero-padded, the padding is masked out through `input_lengths`.
+fn text_encoder_inputs(
+    phoneme_batch: &[Vec<i64>],
+    speaker_id: Option<i64>,
+    synth_config: &PiperSynthesisConfig,
+    float_type: FloatType,
+) -> PiperResult<Vec<SessionInputValue<'static>>> {
+    let batch_size = phoneme_batch.len();
+    let max_len = phoneme_batch.iter().map(Vec::len).max().unwrap_or_default();
+    let phoneme_inputs = Array2::<i64>::from_shape_fn((batch_size, max_len), |(row, col)| {
+        phoneme_batch[row].get(col).copied().unwrap_or(0)
+    });
+    let input_lengths = Array1::<i64>::from_iter(phoneme_batch.iter().map(|ids| ids.len() as i64));
+    let scales = Array1::<f32>::from_iter([
+        synth_config.noise_scale,
+        synth_config.length_scale,
+        synth_config.noise_w,
+    ]);
+    let input_error = |e: ort::Error| PiperError::Inference {
+        message: "Failed to create model inputs".to_string(),
+        source: e,
+    };
+
+    let mut inputs = vec![
+        SessionInputValue::from(Value::from_array(phoneme_inputs).map_err(input_error)?),
+        SessionInputValue::from(Value::from_array(input_lengths).map_err(input_error)?),
+        float_input(scales.view().into_dyn(), float_type).map_err(input_error)?,
+    ];
+    if let Some(sid) = speaker_id {
+        let speaker_ids = Array1::<i64>::from_elem(batch_size, sid);
+        inputs.push(SessionInputValue::from(
+            Value::from_array(speaker_ids).map_err(input_error)?,
+        ));
+    }
+    Ok(inputs)
+}
+
+/// Creates a float input of a session, converted to half precision for fp16 exports
+fn float_input(
+    array: ArrayView<f32, Dim<IxDynImpl>>,
+    float_type: FloatType,
+) -> ort::Result<SessionInputValue<'static>> {
+    Ok(match float_type {
+        FloatType::Float32 => Value::from_array(array)?.into(),
+        FloatType::Float16 => Value::from_array(array.mapv(f16::from_f32))?.into(),
+    })
+}
+
+/// Reads a float output of a session as f32. Only half precision outputs are copied.
+fn float_output(
+    value: &DynValue,
+    float_type: FloatType,
+) -> PiperResult<CowArray<'_, f32, Dim<IxDynImpl>>> {
+    Ok(match float_type {
+        FloatType::Float32 => value
+            .try_extract_tensor::<f32>()
+            .map_err(inference_error)?
+            .into(),
+        FloatType::Float16 => value
+            .try_extract_tensor::<f16>()
+            .map_err(inference_error)?
+            .mapv(f16::to_f32)
+            .into(),
+    })
+}
+
+fn inference_error(e: ort::Error) -> PiperError {
+    PiperError::Inference {
+        message: "Failed to run model inference".to_string(),
+        source: e,
+    }
+}
+
+/// Reads the per-phoneme durations exported by some models, if present
+fn extract_durations(
+    outputs: &SessionOutputs,
+    float_type: FloatType,
+) -> PiperResult<Option<Array<f32, Dim<IxDynImpl>>>> {
+    for name in DURATION_OUTPUT_NAMES {
+        if outputs.contains_key(name) {
+            let durations = float_output(&outputs[name], float_type)?;
+            return Ok(Some(durations.into_owned()));
+        }
+    }
+    Ok(None)
+}
+
+/// Runs the last phoneme id the config declares through the model.
+/// onnxruntime does not expose the size of the phoneme embedding table, but looking up an id
+/// past its end fails, so a model embedding fewer symbols than its config is caught when loaded.
+fn probe_num_symbols(
+    session: &Session,
+    session_info: &SessionInfo,
+    config: &ModelConfig,
+    float_type: FloatType,
+) -> PiperResult<()> {
+    let last_id = i64::from(config.num_symbols.saturating_sub(1));
+    let synth_config = PiperSynthesisConfig {
+        speaker: None,
+        noise_scale: 0.0,
+        length_scale: 1.0,
+        noise_w: 0.0,
+    };
+    let speaker_id = session_info.has_speaker_input().then_some(0);
+    let inputs = text_encoder_inputs(&[vec![last_id]], speaker_id, &synth_config, float_type)?;
+    match session.run(SessionInputs::from(inputs.as_slice())) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(PiperError::ModelMismatch(format!(
+            "The `{}` model failed to run on phoneme id {}, the last of the {} symbols its config declares: {}",
+            session_info.name, last_id, config.num_symbols, e
+        ))),
+    }
+}
+
+/// Warns when a `batch_size` above one was asked for but the model can only run one sentence at a time
+/// Language code of a voice, or its eSpeak voice if the config has none
+fn config_language(config: &ModelConfig) -> String {
+    config
+        .language
+        .as_ref()
+        .map_or_else(|| config.espeak.voice.clone(), |lang| lang.code.clone())
+}
+
+fn warn_batching_unavailable(voice_options: &VoiceOptions) {
+    if let Some(batch_size @ 2..) = voice_options.batch_size {
+        tracing::warn!(
+            batch_size,
+            "the model has a fixed batch dimension or no output to trim batched audio with, sentences are synthesized one at a time"
+        );
+    }
+}
+
+/// Number of valid samples of each item of a batched run, from the predicted duration of
+/// its phonemes. Mirrors the export, which decodes at least one frame per item.
+fn batch_lengths_from_durations(
+    durations: ArrayView<f32, Dim<IxDynImpl>>,
+    phoneme_batch: &[Vec<i64>],
+    hop_length: usize,
+) -> Vec<usize> {
+    Vec::from_iter(durations.axis_iter(Axis(0)).zip(phoneme_batch).map(
+        |(item_durations, phoneme_ids)| {
+            let num_frames: f32 = item_durations
+                .iter()
+                .take(phoneme_ids.len())
+                .map(|duration| duration.max(0.0).ceil())
+                .sum();
+            (num_frames as usize).max(1) * hop_length
+        },
+    ))
+}
+
+/// Cuts the padded audio of each batch item, shaped `[batch, 1, samples]`, to its valid length
+fn trim_batch_audio(audio: ArrayView<f32, Dim<IxDynImpl>>, lengths: &[usize]) -> Vec<Vec<f32>> {
+    Vec::from_iter(
+        audio
+            .axis_iter(Axis(0))
+            .zip(lengths)
+            .map(|(audio, len)| Vec::from_iter(audio.iter().copied().take(*len))),
+    )
+}
+
 pub struct VitsModel {
     synth_config: RwLock<PiperSynthesisConfig>,
     config: ModelConfig,
     speaker_map: HashMap<i64, String>,
+    phoneme_id_config: PhonemeIdConfig,
+    text_normalizer: Option<Arc<dyn TextNormalizer>>,
+    batch_size: usize,
+    batch_lengths: Option<BatchOutputLengths>,
+    hop_length: usize,
+    float_types: FloatTypes,
+    speaker_input: bool,
     session: Session,
 }
 
 impl VitsModel {
     pub fn new(config_path: PathBuf, onnx_path: &Path) -> PiperResult<Self> {
+        Self::new_with_options(config_path, onnx_path, &ModelOptions::default())
+    }
+    pub fn new_with_options(
+        config_path: PathBuf,
+        onnx_path: &Path,
+        options: &ModelOptions,
+    ) -> PiperResult<Self> {
         match load_model_config(&config_path) {
-            Ok((config, synth_config)) => Self::from_config(config, synth_config, onnx_path),
+            Ok((config, synth_config)) => Self::from_config(
+                config,
+                synth_config,
+                ModelSource::File(onnx_path),
+                options,
+                &VoiceOptions::default(),
+            ),
             Err(error) => Err(error),
         }
     }
+    pub fn from_bytes(config_json: &[u8], onnx_bytes: &[u8]) -> PiperResult<Self> {
+        Self::from_bytes_with_options(config_json, onnx_bytes, &ModelOptions::default())
+    }
+    pub fn from_bytes_with_options(
+        config_json: &[u8],
+        onnx_bytes: &[u8],
+        options: &ModelOptions,
+    ) -> PiperResult<Self> {
+        let (config, synth_config) = load_model_config_from_bytes(config_json)?;
+        if config.streaming.unwrap_or_default() {
+            return Err(PiperError::OperationError(
+                "The given config describes a streaming model. Use `VitsStreamingModel::from_bytes` to load it with its encoder and decoder".to_string(),
+            ));
+        }
+        Self::from_config(
+            config,
+            synth_config,
+            ModelSource::Memory(onnx_bytes),
+            options,
+            &VoiceOptions::default(),
+        )
+    }
     fn from_config(
         config: ModelConfig,
         synth_config: PiperSynthesisConfig,
-        onnx_path: &Path,
+        onnx_source: ModelSource,
+        options: &ModelOptions,
+        voice_options: &VoiceOptions,
     ) -> PiperResult<Self> {
-        let session = match create_inference_session(onnx_path) {
+        let session = match create_inference_session(onnx_source, options, None) {
             Ok(session) => session,
             Err(err) => {
-                return Err(PiperError::OperationError(format!(
-                    "Failed to initialize onnxruntime inference session: `{}`",
-                    err
-                )))
+                return Err(PiperError::Inference {
+                    message: "Failed to initialize onnxruntime inference session".to_string(),
+                    source: err,
+                })
+            }
+        };
+        let model_info = SessionInfo::from_session("model", &session)?;
+        model_info::validate_vits_model(&config, &model_info)?;
+        let float_types = model_info.float_types()?;
+        let speaker_input = model_info.has_speaker_input();
+        probe_num_symbols(&session, &model_info, &config, float_types.inputs)?;
+        let batch_lengths = model_info.batch_output_lengths();
+        let batch_size = match batch_lengths {
+            Some(_) => voice_options.batch_size.unwrap_or(1).max(1),
+            None => {
+                warn_batching_unavailable(voice_options);
+                1
             }
         };
+        let hop_length = config.audio.hop_length.unwrap_or(HOP_LENGTH);
         let speaker_map = reversed_mapping(&config.speaker_id_map);
+        let text_normalizer = voice_options
+            .text_normalizer
+            .resolve(&config_language(&config));
 
         Ok(Self {
             synth_config: RwLock::new(synth_config),
             config,
             speaker_map,
+            phoneme_id_config: voice_options.phoneme_ids.clone(),
+            text_normalizer,
+            batch_size,
+            batch_lengths,
+            hop_length,
+            float_types,
+            speaker_input,
             session,
         })
     }
-    fn infer_with_values(&self, input_phonemes: Vec<i64>) -> PiperAudioResult {
-        let synth_config = self.synth_config.read().unwrap();
-
-        let input_len = input_phonemes.len();
-        let phoneme_inputs = Array2::<i64>::from_shape_vec((1, input_len), input_phonemes).unwrap();
-        let input_lengths = Array1::<i64>::from_iter([input_len as i64]);
-        let scales = Array1::<f32>::from_iter([
-            synth_config.noise_scale,
-            synth_config.length_scale,
-            synth_config.noise_w,
-        ]);
-        let speaker_id = if self.config.num_speakers > 1 {
-            let sid = synth_config.speaker.unwrap_or(0);
-            Some(Array1::<i64>::from_iter([sid]))
-        } else {
-            None
-        };
+    fn infer_with_values(
+        &self,
+        input_phonemes: Vec<i64>,
+        synth_config: &PiperSynthesisConfig,
+    ) -> PiperAudioResult {
+        let _span =
+            tracing::debug_span!("inference", num_phonemes = input_phonemes.len()).entered();
+        let speaker_id = self.resolve_speaker_id(synth_config)?;
+        let phoneme_batch = [input_phonemes];
+        let inputs = text_encoder_inputs(
+            &phoneme_batch,
+            speaker_id,
+            synth_config,
+            self.float_types.inputs,
+        )?;
 
         let session = &self.session;
-        let timer = std::time::Instant::now();
+        let timer = Instant::now();
 
         // Scope SessionOutputs to release ONNX memory immediately after copying audio
-        let audio = {
-            let mut inputs = vec![
-                SessionInputValue::from(Value::from_array(phoneme_inputs).unwrap()),
-                SessionInputValue::from(Value::from_array(input_lengths).unwrap()),
-                SessionInputValue::from(Value::from_array(scales).unwrap()),
-            ];
-            if let Some(sid_tensor) = speaker_id {
-                inputs.push(SessionInputValue::from(
-                    Value::from_array(sid_tensor).unwrap(),
-                ));
-            }
+        let (audio, durations) = {
             let outputs = match session.run(SessionInputs::from(inputs.as_slice())) {
                 Ok(out) => out,
-                Err(e) => {
-                    return Err(PiperError::OperationError(format!(
-                        "Failed to run model inference. Error: {}",
-                        e
-                    )))
-                }
+                Err(e) => return Err(inference_error(e)),
             };
 
-            let output_tensor = match outputs[0].try_extract_tensor::<f32>() {
-                Ok(out) => out,
-                Err(e) => {
-                    return Err(PiperError::OperationError(format!(
-                        "Failed to run model inference. Error: {}",
-                        e
-                    )))
-                }
-            };
+            let output_tensor = float_output(&outputs[0], self.float_types.outputs)?;
 
             // Copy audio immediately, then outputs drops at end of scope
-            Vec::from(output_tensor.view().as_slice().unwrap())
+            (
+                audio_tensor_to_vec(output_tensor.view())?,
+                extract_durations(&outputs, self.float_types.outputs)?,
+            )
         }; // SessionOutputs dropped here, releasing ONNX memory
 
-        let inference_ms = timer.elapsed().as_millis() as f32;
+        let elapsed = timer.elapsed();
+        tracing::debug!(?elapsed, num_samples = audio.len(), "model run finished");
 
-        Ok(Audio::new(
+        let mut audio = Audio::new(
             audio.into(),
             self.config.audio.sample_rate as usize,
-            Some(inference_ms),
-        ))
+            Some(elapsed.as_millis() as f32),
+        );
+        audio.alignment =
+            durations.map(|durations| self.phoneme_alignment(&phoneme_batch[0], durations.view()));
+        audio.timings.decoder = Some(elapsed);
+        Ok(audio)
     }
-    pub fn get_input_output_info(&self) -> PiperResult<Vec<String>> {
-        todo!()
+    /// Runs several phoneme id sequences through the model at once,
+    /// then trims each padded audio output to its own length
+    fn infer_batch(
+        &self,
+        phoneme_batch: &[Vec<i64>],
+        synth_config: &PiperSynthesisConfig,
+        batch_lengths: BatchOutputLengths,
+    ) -> PiperResult<Vec<Audio>> {
+        let _span = tracing::debug_span!("inference", batch_size = phoneme_batch.len()).entered();
+        let speaker_id = self.resolve_speaker_id(synth_config)?;
+        let inputs = text_encoder_inputs(
+            phoneme_batch,
+            speaker_id,
+            synth_config,
+            self.float_types.inputs,
+        )?;
+        let timer = Instant::now();
+
+        let (batch_audio, durations) = {
+            let outputs = self
+                .session
+                .run(SessionInputs::from(inputs.as_slice()))
+                .map_err(inference_error)?;
+            let audio_t = float_output(&outputs[0], self.float_types.outputs)?;
+            let durations = extract_durations(&outputs, self.float_types.outputs)?;
+            let lengths = match (batch_lengths, &durations) {
+                (BatchOutputLengths::FrameMask, _) => {
+                    let y_mask = float_output(&outputs["y_mask"], self.float_types.outputs)?;
+                    Vec::from_iter(
+                        y_mask
+                            .axis_iter(Axis(0))
+                            .map(|mask| mask.sum().round() as usize * self.hop_length),
+                    )
+                }
+                (BatchOutputLengths::Samples, _) => {
+                    let output_lengths = outputs["output_lengths"]
+                        .try_extract_tensor::<i64>()
+                        .map_err(inference_error)?;
+                    Vec::from_iter(output_lengths.iter().map(|len| (*len).max(0) as usize))
+                }
+                (BatchOutputLengths::Durations, Some(durations)) => {
+                    batch_lengths_from_durations(durations.view(), phoneme_batch, self.hop_length)
+                }
+                (BatchOutputLengths::Durations, None) => {
+                    return Err(PiperError::TensorShape(
+                        "The model has no phoneme duration output to trim its batched audio with"
+                            .to_string(),
+                    ))
+                }
+            };
+            (trim_batch_audio(audio_t.view(), &lengths), durations)
+        };
+
+        let elapsed = timer.elapsed();
+        tracing::debug!(?elapsed, "batched model run finished");
+        // The run is shared by the whole batch, so split its time evenly
+        let inference_ms = elapsed.as_millis() as f32 / phoneme_batch.len() as f32;
+        let elapsed = elapsed / phoneme_batch.len() as u32;
+        Ok(Vec::from_iter(batch_audio.into_iter().enumerate().map(
+            |(index, audio)| {
+                let mut audio = Audio::new(
+                    audio.into(),
+                    self.config.audio.sample_rate as usize,
+                    Some(inference_ms),
+                );
+                audio.timings.decoder = Some(elapsed);
+                audio.alignment = durations.as_ref().map(|durations| {
+                    self.phoneme_alignment(
+                        &phoneme_batch[index],
+                        durations.index_axis(Axis(0), index).into_dyn(),
+                    )
+                });
+                audio
+            },
+        )))
+    }
+    pub fn get_input_output_info(&self) -> PiperResult<Vec<SessionInfo>> {
+        Ok(vec![SessionInfo::from_session("model", &self.session)?])
     }
 }
 
@@ -376,6 +1338,18 @@ impl VitsModelCommons for VitsModel {
     fn get_speaker_map(&self) -> &HashMap<i64, String> {
         &self.speaker_map
     }
+    fn get_phoneme_id_config(&self) -> &PhonemeIdConfig {
+        &self.phoneme_id_config
+    }
+    fn get_text_normalizer(&self) -> Option<&dyn TextNormalizer> {
+        self.text_normalizer.as_deref()
+    }
+    fn hop_length(&self) -> usize {
+        self.hop_length
+    }
+    fn has_speaker_input(&self) -> bool {
+        self.speaker_input
+    }
 }
 
 impl PiperModel for VitsModel {
@@ -384,42 +1358,67 @@ impl PiperModel for VitsModel {
     }
 
     fn speak_batch(&self, phoneme_batches: Vec<String>) -> PiperResult<Vec<Audio>> {
-        let (pad_id, bos_id, eos_id) = self.get_meta_ids();
-        let phoneme_batches = Vec::from_iter(
-            phoneme_batches
-                .into_iter()
-                .map(|phonemes| self.phonemes_to_input_ids(&phonemes, pad_id, bos_id, eos_id)),
-        );
-        let mut retval = Vec::new();
-        for phonemes in phoneme_batches.into_iter() {
-            retval.push(self.infer_with_values(phonemes)?);
+        self.speak_batch_with_config(phoneme_batches, &self.fallback_synth_config())
+    }
+    fn speak_batch_with_config(
+        &self,
+        phoneme_batches: Vec<String>,
+        synthesis_config: &PiperSynthesisConfig,
+    ) -> PiperResult<Vec<Audio>> {
+        let (phoneme_batch, ids_timings): (Vec<_>, Vec<_>) = phoneme_batches
+            .iter()
+            .map(|phonemes| self.timed_input_ids(phonemes))
+            .collect::<PiperResult<Vec<_>>>()?
+            .into_iter()
+            .unzip();
+        let mut retval = Vec::with_capacity(phoneme_batch.len());
+        match self.batch_lengths {
+            Some(batch_lengths) if self.batch_size > 1 => {
+                for batch in phoneme_batch.chunks(self.batch_size) {
+                    retval.extend(self.infer_batch(batch, synthesis_config, batch_lengths)?);
+                }
+            }
+            _ => {
+                for phoneme_ids in phoneme_batch.into_iter() {
+                    retval.push(self.infer_with_values(phoneme_ids, synthesis_config)?);
+                }
+            }
+        }
+        for (audio, ids_elapsed) in retval.iter_mut().zip(ids_timings) {
+            audio.timings.phoneme_ids = Some(ids_elapsed);
         }
         Ok(retval)
     }
 
     fn speak_one_sentence(&self, phonemes: String) -> PiperAudioResult {
-        let (pad_id, bos_id, eos_id) = self.get_meta_ids();
-        let phonemes = self.phonemes_to_input_ids(&phonemes, pad_id, bos_id, eos_id);
-        self.infer_with_values(phonemes)
+        self.speak_one_sentence_with_config(phonemes, &self.fallback_synth_config())
+    }
+    fn speak_one_sentence_with_config(
+        &self,
+        phonemes: String,
+        synthesis_config: &PiperSynthesisConfig,
+    ) -> PiperAudioResult {
+        let (phoneme_ids, ids_elapsed) = self.timed_input_ids(&phonemes)?;
+        let mut audio = self.infer_with_values(phoneme_ids, synthesis_config)?;
+        audio.timings.phoneme_ids = Some(ids_elapsed);
+        Ok(audio)
     }
-    fn get_default_synthesis_config(&self) -> PiperResult<Box<dyn Any>> {
-        Ok(Box::new(PiperSynthesisConfig {
-            speaker: Some(0),
+    fn get_default_synthesis_config(&self) -> PiperResult<PiperSynthesisConfig> {
+        Ok(PiperSynthesisConfig {
+            speaker: Some(Speaker::Id(0)),
             noise_scale: self.config.inference.noise_scale,
             noise_w: self.config.inference.noise_w,
             length_scale: self.config.inference.length_scale,
-        }))
+        })
     }
-    fn get_fallback_synthesis_config(&self) -> PiperResult<Box<dyn Any>> {
-        Ok(Box::new(self.synth_config.read().unwrap().clone()))
+    fn get_fallback_synthesis_config(&self) -> PiperResult<PiperSynthesisConfig> {
+        Ok(self.fallback_synth_config())
     }
-    fn set_fallback_synthesis_config(&self, synthesis_config: &dyn Any) -> PiperResult<()> {
-        match synthesis_config.downcast_ref::<PiperSynthesisConfig>() {
-            Some(new_config) => self._do_set_default_synth_config(new_config),
-            None => Err(PiperError::OperationError(
-                "Invalid configuration for Vits Model".to_string(),
-            )),
-        }
+    fn set_fallback_synthesis_config(
+        &self,
+        synthesis_config: &PiperSynthesisConfig,
+    ) -> PiperResult<()> {
+        self._do_set_default_synth_config(synthesis_config)
     }
     fn get_language(&self) -> PiperResult<Option<String>> {
         Ok(self.language())
@@ -436,106 +1435,471 @@ impl PiperModel for VitsModel {
     fn properties(&self) -> PiperResult<HashMap<String, String>> {
         Ok(self.get_properties())
     }
+    fn get_input_output_info(&self) -> PiperResult<Vec<SessionInfo>> {
+        Self::get_input_output_info(self)
+    }
+    fn phonemes_to_ids(&self, phonemes: &str) -> PiperResult<PhonemeIds> {
+        self.phonemes_to_input_ids(phonemes)
+    }
     fn audio_output_info(&self) -> PiperResult<AudioInfo> {
         self.get_audio_output_info()
     }
+    fn warm_up(&self) -> PiperResult<WarmUpTimings> {
+        warm_up_model(self, false)
+    }
+    fn supports_streaming_output(&self) -> bool {
+        true
+    }
+    /// Streams the sentence one clause at a time. `chunk_size` and `chunk_padding`
+    /// are ignored, since the model can only produce the audio of a whole input.
+    fn stream_synthesis(
+        &self,
+        phonemes: String,
+        chunk_size: usize,
+        chunk_padding: usize,
+    ) -> PiperResult<AudioStreamIterator<'_>> {
+        self.stream_synthesis_with_config(
+            phonemes,
+            &self.fallback_synth_config(),
+            chunk_size,
+            chunk_padding,
+        )
+    }
+    fn stream_synthesis_with_config(
+        &self,
+        phonemes: String,
+        synthesis_config: &PiperSynthesisConfig,
+        _chunk_size: usize,
+        _chunk_padding: usize,
+    ) -> PiperResult<AudioStreamIterator<'_>> {
+        let timer = Instant::now();
+        let clauses = split_clauses(&phonemes)
+            .into_iter()
+            .map(|clause| Ok(self.phonemes_to_input_ids(clause)?.ids))
+            .collect::<PiperResult<Vec<_>>>()?;
+        let timings = SynthesisTimings {
+            phoneme_ids: Some(timer.elapsed()),
+            ..Default::default()
+        };
+        Ok(Box::new(ClauseStreamer {
+            model: self,
+            synth_config: synthesis_config.clone(),
+            clauses: clauses.into_iter(),
+            tail: Vec::new(),
+            position: 0,
+            fade_samples: CLAUSE_CROSSFADE_MS * self.config.audio.sample_rate as usize / 1000,
+            timings,
+        }))
+    }
+}
+
+/// Shared [`PiperModel::warm_up`] of both VITS models.
+/// Only `VitsStreamingModel` has decoder chunks worth exercising.
+fn warm_up_model<M>(model: &M, stream_decoder_chunks: bool) -> PiperResult<WarmUpTimings>
+where
+    M: PiperModel + VitsModelCommons,
+{
+    let _span = tracing::info_span!("warm_up").entered();
+    let started = Instant::now();
+    model.phonemize_text(WARM_UP_TEXT)?;
+    let phonemization = started.elapsed();
+
+    let synth_config = model.fallback_synth_config();
+    let mut sentences = Vec::with_capacity(WARM_UP_SENTENCE_LENGTHS.len());
+    for num_phonemes in WARM_UP_SENTENCE_LENGTHS {
+        let timer = Instant::now();
+        let _ = model
+            .speak_one_sentence_with_config(model.warm_up_phonemes(num_phonemes), &synth_config)?;
+        sentences.push((num_phonemes, timer.elapsed()));
+    }
+    let mut decoder_chunks = Vec::new();
+    if stream_decoder_chunks {
+        let num_phonemes = WARM_UP_SENTENCE_LENGTHS[WARM_UP_SENTENCE_LENGTHS.len() - 1];
+        let stream = model.stream_synthesis_with_config(
+            model.warm_up_phonemes(num_phonemes),
+            &synth_config,
+            WARM_UP_CHUNK_SIZE,
+            WARM_UP_CHUNK_PADDING,
+        )?;
+        for chunk in stream {
+            decoder_chunks.push(chunk?.timings.decoder.unwrap_or_default());
+        }
+    }
+    let timings = WarmUpTimings {
+        phonemization,
+        sentences,
+        decoder_chunks,
+        total: started.elapsed(),
+    };
+    tracing::info!(total = ?timings.total, "model warmed up");
+    Ok(timings)
+}
+
+/// Streams a sentence of a non-streaming model by running inference on each clause
+/// and crossfading the end of every clause into the start of the next one
+struct ClauseStreamer<'a> {
+    model: &'a VitsModel,
+    synth_config: PiperSynthesisConfig,
+    clauses: std::vec::IntoIter<Vec<i64>>,
+    /// End of the previous clause, held back to be mixed with the next one
+    tail: Vec<f32>,
+    /// Number of samples handed out so far
+    position: usize,
+    fade_samples: usize,
+    /// Handed out with the first chunk
+    timings: SynthesisTimings,
+}
+
+impl Iterator for ClauseStreamer<'_> {
+    type Item = PiperResult<AudioChunk>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Some(phoneme_ids) = self.clauses.next() else {
+            // Flush the end of the last clause
+            if self.tail.is_empty() {
+                return None;
+            }
+            let samples = std::mem::take(&mut self.tail);
+            self.position += samples.len();
+            return Some(Ok(AudioChunk::from(AudioSamples::from(samples))));
+        };
+        let audio = match self
+            .model
+            .infer_with_values(phoneme_ids, &self.synth_config)
+        {
+            Ok(audio) => audio,
+            Err(e) => return Some(Err(e)),
+        };
+        // A tail longer than the clause is only mixed with it at its end
+        let lead = self.tail.len().saturating_sub(audio.samples.len());
+        let offset_ms = (self.position + lead) as f32 * 1000.0 / audio.info.sample_rate as f32;
+        let alignment = audio.alignment.map(|mut alignment| {
+            for phoneme in alignment.iter_mut() {
+                phoneme.start_ms += offset_ms;
+                phoneme.end_ms += offset_ms;
+            }
+            alignment
+        });
+        let timings = SynthesisTimings {
+            decoder: audio.timings.decoder,
+            ..std::mem::take(&mut self.timings)
+        };
+        let mut mixed = crossfade_clauses(std::mem::take(&mut self.tail), audio.samples.into_vec());
+        let hold = if self.clauses.as_slice().is_empty() {
+            0
+        } else {
+            self.fade_samples.min(mixed.len() / 2)
+        };
+        self.tail = mixed.split_off(mixed.len() - hold);
+        self.position += mixed.len();
+        Some(Ok(AudioChunk {
+            samples: mixed.into(),
+            alignment,
+            timings,
+        }))
+    }
+}
+
+/// Crossfades the end of the previous clause's `tail` into the start of `clause`.
+/// The part of the tail that is longer than the clause comes first, unmixed.
+fn crossfade_clauses(mut tail: Vec<f32>, mut clause: Vec<f32>) -> Vec<f32> {
+    let overlap = tail.len().min(clause.len());
+    let previous = tail.split_off(tail.len() - overlap);
+    for (i, (sample, previous)) in clause.iter_mut().zip(&previous).enumerate() {
+        // Equal power crossfade
+        let t = (i as f32 + 0.5) / overlap as f32 * std::f32::consts::FRAC_PI_2;
+        *sample = *sample * t.sin() + previous * t.cos();
+    }
+    tail.append(&mut clause);
+    tail
 }
 
 pub struct VitsStreamingModel {
     synth_config: RwLock<PiperSynthesisConfig>,
     config: ModelConfig,
     speaker_map: HashMap<i64, String>,
+    phoneme_id_config: PhonemeIdConfig,
+    text_normalizer: Option<Arc<dyn TextNormalizer>>,
+    batch_size: usize,
+    hop_length: usize,
+    mel_chunker: MelChunkerConfig,
+    speaker_input: bool,
     encoder_model: Session,
-    decoder_model: Arc<Session>,
+    encoder_float_types: FloatTypes,
+    decoder_model: Arc<Decoder>,
+}
+
+/// Decoder session of a streaming voice, shared with the streams decoding its output
+struct Decoder {
+    session: Session,
+    float_types: FloatTypes,
 }
 
 impl VitsStreamingModel {
+    pub fn from_bytes(
+        config_json: &[u8],
+        encoder_bytes: &[u8],
+        decoder_bytes: &[u8],
+    ) -> PiperResult<Self> {
+        Self::from_bytes_with_options(
+            config_json,
+            encoder_bytes,
+            decoder_bytes,
+            &ModelOptions::default(),
+        )
+    }
+    pub fn from_bytes_with_options(
+        config_json: &[u8],
+        encoder_bytes: &[u8],
+        decoder_bytes: &[u8],
+        options: &ModelOptions,
+    ) -> PiperResult<Self> {
+        let (config, synth_config) = load_model_config_from_bytes(config_json)?;
+        if !config.streaming.unwrap_or_default() {
+            return Err(PiperError::OperationError(
+                "The given config does not describe a streaming model. Use `VitsModel::from_bytes` to load it from a single ONNX model".to_string(),
+            ));
+        }
+        Self::from_config(
+            config,
+            synth_config,
+            ModelSource::Memory(encoder_bytes),
+            ModelSource::Memory(decoder_bytes),
+            options,
+            &VoiceOptions::default(),
+        )
+    }
     fn from_config(
         config: ModelConfig,
         synth_config: PiperSynthesisConfig,
-        encoder_path: &Path,
-        decoder_path: &Path,
+        encoder_source: ModelSource,
+        decoder_source: ModelSource,
+        options: &ModelOptions,
+        voice_options: &VoiceOptions,
     ) -> PiperResult<Self> {
-        let encoder_model = match create_inference_session(encoder_path) {
+        voice_options.mel_chunker.validate()?;
+        let encoder_model = match create_inference_session(encoder_source, options, Some("encoder"))
+        {
             Ok(model) => model,
             Err(err) => {
-                return Err(PiperError::OperationError(format!(
-                    "Failed to initialize onnxruntime inference session: `{}`",
-                    err
-                )))
+                return Err(PiperError::Inference {
+                    message: "Failed to initialize onnxruntime inference session".to_string(),
+                    source: err,
+                })
             }
         };
-        let decoder_model = match create_inference_session(decoder_path) {
-            Ok(model) => Arc::new(model),
+        let decoder_model = match create_inference_session(decoder_source, options, Some("decoder"))
+        {
+            Ok(model) => model,
             Err(err) => {
-                return Err(PiperError::OperationError(format!(
-                    "Failed to initialize onnxruntime inference session: `{}`",
-                    err
-                )))
+                return Err(PiperError::Inference {
+                    message: "Failed to initialize onnxruntime inference session".to_string(),
+                    source: err,
+                })
             }
         };
+        let encoder_info = SessionInfo::from_session("encoder", &encoder_model)?;
+        let decoder_info = SessionInfo::from_session("decoder", &decoder_model)?;
+        model_info::validate_streaming_model(&config, &encoder_info, &decoder_info)?;
+        let encoder_float_types = encoder_info.float_types()?;
+        let speaker_input = encoder_info.has_speaker_input();
+        probe_num_symbols(
+            &encoder_model,
+            &encoder_info,
+            &config,
+            encoder_float_types.inputs,
+        )?;
+        let decoder_model = Arc::new(Decoder {
+            session: decoder_model,
+            float_types: decoder_info.float_types()?,
+        });
+        let hop_length = match config.audio.hop_length {
+            Some(hop_length) => hop_length,
+            None => measure_hop_length(&decoder_model, &decoder_info)?.unwrap_or(HOP_LENGTH),
+        };
+        // Encoder outputs are trimmed with `y_mask` and decoded one sentence at a time
+        let batch_size = if encoder_info.supports_dynamic_batch() {
+            voice_options.batch_size.unwrap_or(1).max(1)
+        } else {
+            warn_batching_unavailable(voice_options);
+            1
+        };
         let speaker_map = reversed_mapping(&config.speaker_id_map);
+        let text_normalizer = voice_options
+            .text_normalizer
+            .resolve(&config_language(&config));
 
         Ok(Self {
             synth_config: RwLock::new(synth_config),
             config,
             speaker_map,
+            phoneme_id_config: voice_options.phoneme_ids.clone(),
+            text_normalizer,
+            batch_size,
+            hop_length,
+            mel_chunker: voice_options.mel_chunker,
+            speaker_input,
             encoder_model,
+            encoder_float_types,
             decoder_model,
         })
     }
 
-    fn infer_with_values(&self, input_phonemes: Vec<i64>) -> PiperAudioResult {
-        let timer = std::time::Instant::now();
-        let encoder_output = self.infer_encoder(input_phonemes)?;
+    fn infer_with_values(
+        &self,
+        input_phonemes: Vec<i64>,
+        synth_config: &PiperSynthesisConfig,
+    ) -> PiperAudioResult {
+        let timer = Instant::now();
+        let phoneme_batch = [input_phonemes];
+        let encoder_output = self.infer_encoder(&phoneme_batch, synth_config)?;
+        let encoder_elapsed = timer.elapsed();
         let audio = encoder_output.infer_decoder(self.decoder_model.as_ref())?;
-        let inference_ms = timer.elapsed().as_millis() as f32;
-        Ok(Audio::new(
+        let elapsed = timer.elapsed();
+        let mut audio = Audio::new(
             audio,
             self.config.audio.sample_rate as usize,
-            Some(inference_ms),
-        ))
+            Some(elapsed.as_millis() as f32),
+        );
+        audio.alignment = encoder_output
+            .p_duration
+            .map(|durations| self.phoneme_alignment(&phoneme_batch[0], durations.view()));
+        audio.timings.encoder = Some(encoder_elapsed);
+        audio.timings.decoder = Some(elapsed - encoder_elapsed);
+        Ok(audio)
     }
-    fn infer_encoder(&self, input_phonemes: Vec<i64>) -> PiperResult<EncoderOutputs> {
-        let synth_config = self.synth_config.read().unwrap();
-
-        let input_len = input_phonemes.len();
-        let phoneme_inputs = Array2::<i64>::from_shape_vec((1, input_len), input_phonemes).unwrap();
-        let input_lengths = Array1::<i64>::from_iter([input_len as i64]);
-
-        let scales = Array1::<f32>::from_iter([
-            synth_config.noise_scale,
-            synth_config.length_scale,
-            synth_config.noise_w,
-        ]);
-
-        let speaker_id = if self.config.num_speakers > 1 {
-            let sid = synth_config.speaker.unwrap_or(0);
-            Some(Array1::<i64>::from_iter([sid]))
-        } else {
-            None
-        };
-
-        let session = &self.encoder_model;
+    /// Runs several phoneme id sequences through the encoder at once,
+    /// then decodes each sentence separately from its trimmed encoder outputs
+    fn infer_batch(
+        &self,
+        phoneme_batch: &[Vec<i64>],
+        synth_config: &PiperSynthesisConfig,
+    ) -> PiperResult<Vec<Audio>> {
+        let timer = Instant::now();
+        let encoder_outputs = self.infer_encoder(phoneme_batch, synth_config)?;
+        // The encoder run is shared by the whole batch, so split the time evenly
+        let encoder_elapsed = timer.elapsed() / phoneme_batch.len() as u32;
+        let input_lengths = Vec::from_iter(phoneme_batch.iter().map(Vec::len));
+        let mut batch_audio = Vec::with_capacity(phoneme_batch.len());
+        for (item_outputs, phoneme_ids) in encoder_outputs
+            .split(&input_lengths)
+            .into_iter()
+            .zip(phoneme_batch)
         {
-            let mut inputs = vec![
-                SessionInputValue::from(Value::from_array(phoneme_inputs).unwrap()),
-                SessionInputValue::from(Value::from_array(input_lengths).unwrap()),
-                SessionInputValue::from(Value::from_array(scales).unwrap()),
-            ];
-            if let Some(sid_tensor) = speaker_id {
-                inputs.push(SessionInputValue::from(
-                    Value::from_array(sid_tensor).unwrap(),
-                ));
-            }
-            match session.run(SessionInputs::from(inputs.as_slice())) {
-                Ok(ort_values) => EncoderOutputs::from_values(ort_values),
-                Err(e) => Err(PiperError::OperationError(format!(
-                    "Failed to run model inference. Error: {}",
-                    e
-                ))),
+            let decoder_timer = Instant::now();
+            let samples = item_outputs.infer_decoder(self.decoder_model.as_ref())?;
+            let decoder_elapsed = decoder_timer.elapsed();
+            let alignment = item_outputs
+                .p_duration
+                .map(|durations| self.phoneme_alignment(phoneme_ids, durations.view()));
+            batch_audio.push((samples, alignment, decoder_elapsed));
+        }
+        let inference_ms = timer.elapsed().as_millis() as f32 / phoneme_batch.len() as f32;
+        Ok(Vec::from_iter(batch_audio.into_iter().map(
+            |(samples, alignment, decoder_elapsed)| {
+                let mut audio = Audio::new(
+                    samples,
+                    self.config.audio.sample_rate as usize,
+                    Some(inference_ms),
+                );
+                audio.alignment = alignment;
+                audio.timings.encoder = Some(encoder_elapsed);
+                audio.timings.decoder = Some(decoder_elapsed);
+                audio
+            },
+        )))
+    }
+    fn infer_encoder(
+        &self,
+        phoneme_batch: &[Vec<i64>],
+        synth_config: &PiperSynthesisConfig,
+    ) -> PiperResult<EncoderOutputs> {
+        let _span = tracing::debug_span!("encoder", batch_size = phoneme_batch.len()).entered();
+        let speaker_id = self.resolve_speaker_id(synth_config)?;
+        let inputs = text_encoder_inputs(
+            phoneme_batch,
+            speaker_id,
+            synth_config,
+            self.encoder_float_types.inputs,
+        )?;
+        let mut encoder_outputs = match self
+            .encoder_model
+            .run(SessionInputs::from(inputs.as_slice()))
+        {
+            Ok(ort_values) => {
+                EncoderOutputs::from_values(ort_values, self.encoder_float_types.outputs)?
             }
+            Err(e) => return Err(inference_error(e)),
+        };
+        // Only the decoder is conditioned on the custom embedding,
+        // durations and `z` come from the encoder's default speaker
+        if let Some(Speaker::Embedding(ref embedding)) = synth_config.speaker {
+            encoder_outputs.set_speaker_embedding(embedding)?;
+        }
+        Ok(encoder_outputs)
+    }
+    /// Runs the encoder on an empty sentence to read the speaker's conditioning vector
+    pub fn speaker_embedding(&self, speaker: &Speaker) -> PiperResult<SpeakerEmbedding> {
+        if !self.supports_speaker_embeddings() {
+            return Err(PiperError::InvalidSpeaker(
+                "Speaker embeddings are only available for multi-speaker models".to_string(),
+            ));
+        }
+        if let Speaker::Embedding(embedding) = speaker {
+            return Ok(embedding.clone());
+        }
+        let synth_config = PiperSynthesisConfig {
+            speaker: Some(speaker.clone()),
+            ..self.fallback_synth_config()
+        };
+        let phoneme_ids = self.phonemes_to_input_ids("")?.ids;
+        let encoder_outputs = self.infer_encoder(&[phoneme_ids], &synth_config)?;
+        Ok(SpeakerEmbedding::new(Vec::from_iter(
+            encoder_outputs.g.iter().copied(),
+        )))
+    }
+    pub fn get_input_output_info(&self) -> PiperResult<Vec<SessionInfo>> {
+        Ok(vec![
+            SessionInfo::from_session("encoder", &self.encoder_model)?,
+            SessionInfo::from_session("decoder", &self.decoder_model.session)?,
+        ])
+    }
+    fn speech_streamer(
+        &self,
+        phonemes: String,
+        synthesis_config: &PiperSynthesisConfig,
+        chunk_size: usize,
+        chunk_padding: usize,
+    ) -> PiperResult<SpeechStreamer> {
+        if chunk_size == 0 {
+            return Err(PiperError::OperationError(
+                "The chunk size of a stream must be at least one mel frame".to_string(),
+            ));
         }
+        let (phoneme_ids, ids_elapsed) = self.timed_input_ids(&phonemes)?;
+        let phoneme_batch = [phoneme_ids];
+        let timer = Instant::now();
+        let encoder_outputs = self.infer_encoder(&phoneme_batch, synthesis_config)?;
+        let timings = SynthesisTimings {
+            phoneme_ids: Some(ids_elapsed),
+            encoder: Some(timer.elapsed()),
+            ..Default::default()
+        };
+        let alignment = encoder_outputs
+            .p_duration
+            .as_ref()
+            .map(|durations| self.phoneme_alignment(&phoneme_batch[0], durations.view()));
+        let mut streamer = SpeechStreamer::new(
+            Arc::clone(&self.decoder_model),
+            encoder_outputs,
+            alignment,
+            self.mel_chunker,
+            self.hop_length,
+            chunk_size,
+            chunk_padding,
+        );
+        streamer.timings = timings;
+        Ok(streamer)
     }
 }
 
@@ -549,6 +1913,21 @@ impl VitsModelCommons for VitsStreamingModel {
     fn get_speaker_map(&self) -> &HashMap<i64, String> {
         &self.speaker_map
     }
+    fn get_phoneme_id_config(&self) -> &PhonemeIdConfig {
+        &self.phoneme_id_config
+    }
+    fn get_text_normalizer(&self) -> Option<&dyn TextNormalizer> {
+        self.text_normalizer.as_deref()
+    }
+    fn hop_length(&self) -> usize {
+        self.hop_length
+    }
+    fn supports_speaker_embeddings(&self) -> bool {
+        self.config.num_speakers > 1
+    }
+    fn has_speaker_input(&self) -> bool {
+        self.speaker_input
+    }
 }
 
 impl PiperModel for VitsStreamingModel {
@@ -557,41 +1936,63 @@ impl PiperModel for VitsStreamingModel {
     }
 
     fn speak_batch(&self, phoneme_batches: Vec<String>) -> PiperResult<Vec<Audio>> {
-        let (pad_id, bos_id, eos_id) = self.get_meta_ids();
-        let phoneme_batches = Vec::from_iter(
-            phoneme_batches
-                .into_iter()
-                .map(|phonemes| self.phonemes_to_input_ids(&phonemes, pad_id, bos_id, eos_id)),
-        );
-        let mut retval = Vec::new();
-        for phonemes in phoneme_batches.into_iter() {
-            retval.push(self.infer_with_values(phonemes)?);
+        self.speak_batch_with_config(phoneme_batches, &self.fallback_synth_config())
+    }
+    fn speak_batch_with_config(
+        &self,
+        phoneme_batches: Vec<String>,
+        synthesis_config: &PiperSynthesisConfig,
+    ) -> PiperResult<Vec<Audio>> {
+        let (phoneme_batch, ids_timings): (Vec<_>, Vec<_>) = phoneme_batches
+            .iter()
+            .map(|phonemes| self.timed_input_ids(phonemes))
+            .collect::<PiperResult<Vec<_>>>()?
+            .into_iter()
+            .unzip();
+        let mut retval = Vec::with_capacity(phoneme_batch.len());
+        if self.batch_size > 1 {
+            for batch in phoneme_batch.chunks(self.batch_size) {
+                retval.extend(self.infer_batch(batch, synthesis_config)?);
+            }
+        } else {
+            for phoneme_ids in phoneme_batch.into_iter() {
+                retval.push(self.infer_with_values(phoneme_ids, synthesis_config)?);
+            }
+        }
+        for (audio, ids_elapsed) in retval.iter_mut().zip(ids_timings) {
+            audio.timings.phoneme_ids = Some(ids_elapsed);
         }
         Ok(retval)
     }
     fn speak_one_sentence(&self, phonemes: String) -> PiperAudioResult {
-        let (pad_id, bos_id, eos_id) = self.get_meta_ids();
-        let phonemes = self.phonemes_to_input_ids(&phonemes, pad_id, bos_id, eos_id);
-        self.infer_with_values(phonemes)
+        self.speak_one_sentence_with_config(phonemes, &self.fallback_synth_config())
+    }
+    fn speak_one_sentence_with_config(
+        &self,
+        phonemes: String,
+        synthesis_config: &PiperSynthesisConfig,
+    ) -> PiperAudioResult {
+        let (phoneme_ids, ids_elapsed) = self.timed_input_ids(&phonemes)?;
+        let mut audio = self.infer_with_values(phoneme_ids, synthesis_config)?;
+        audio.timings.phoneme_ids = Some(ids_elapsed);
+        Ok(audio)
     }
-    fn get_default_synthesis_config(&self) -> PiperResult<Box<dyn Any>> {
-        Ok(Box::new(PiperSynthesisConfig {
-            speaker: Some(0),
+    fn get_default_synthesis_config(&self) -> PiperResult<PiperSynthesisConfig> {
+        Ok(PiperSynthesisConfig {
+            speaker: Some(Speaker::Id(0)),
             noise_scale: self.config.inference.noise_scale,
             noise_w: self.config.inference.noise_w,
             length_scale: self.config.inference.length_scale,
-        }))
+        })
     }
-    fn get_fallback_synthesis_config(&self) -> PiperResult<Box<dyn Any>> {
-        Ok(Box::new(self.synth_config.read().unwrap().clone()))
+    fn get_fallback_synthesis_config(&self) -> PiperResult<PiperSynthesisConfig> {
+        Ok(self.fallback_synth_config())
     }
-    fn set_fallback_synthesis_config(&self, synthesis_config: &dyn Any) -> PiperResult<()> {
-        match synthesis_config.downcast_ref::<PiperSynthesisConfig>() {
-            Some(new_config) => self._do_set_default_synth_config(new_config),
-            None => Err(PiperError::OperationError(
-                "Invalid configuration for Vits Model".to_string(),
-            )),
-        }
+    fn set_fallback_synthesis_config(
+        &self,
+        synthesis_config: &PiperSynthesisConfig,
+    ) -> PiperResult<()> {
+        self._do_set_default_synth_config(synthesis_config)
     }
     fn get_language(&self) -> PiperResult<Option<String>> {
         Ok(self.language())
@@ -608,9 +2009,21 @@ impl PiperModel for VitsStreamingModel {
     fn properties(&self) -> PiperResult<HashMap<String, String>> {
         Ok(self.get_properties())
     }
+    fn get_input_output_info(&self) -> PiperResult<Vec<SessionInfo>> {
+        Self::get_input_output_info(self)
+    }
+    fn phonemes_to_ids(&self, phonemes: &str) -> PiperResult<PhonemeIds> {
+        self.phonemes_to_input_ids(phonemes)
+    }
+    fn speaker_embedding(&self, speaker: &Speaker) -> PiperResult<SpeakerEmbedding> {
+        Self::speaker_embedding(self, speaker)
+    }
     fn audio_output_info(&self) -> PiperResult<AudioInfo> {
         self.get_audio_output_info()
     }
+    fn warm_up(&self) -> PiperResult<WarmUpTimings> {
+        warm_up_model(self, true)
+    }
     fn supports_streaming_output(&self) -> bool {
         true
     }
@@ -619,80 +2032,104 @@ impl PiperModel for VitsStreamingModel {
         phonemes: String,
         chunk_size: usize,
         chunk_padding: usize,
-    ) -> PiperResult<AudioStreamIterator> {
-        let (pad_id, bos_id, eos_id) = self.get_meta_ids();
-        let phonemes = self.phonemes_to_input_ids(&phonemes, pad_id, bos_id, eos_id);
-        let encoder_outputs = self.infer_encoder(phonemes)?;
-        let streamer = Box::new(SpeechStreamer::new(
-            Arc::clone(&self.decoder_model),
-            encoder_outputs,
+    ) -> PiperResult<AudioStreamIterator<'_>> {
+        self.stream_synthesis_with_config(
+            phonemes,
+            &self.fallback_synth_config(),
             chunk_size,
             chunk_padding,
-        ));
-        Ok(streamer)
+        )
+    }
+    fn stream_synthesis_with_config(
+        &self,
+        phonemes: String,
+        synthesis_config: &PiperSynthesisConfig,
+        chunk_size: usize,
+        chunk_padding: usize,
+    ) -> PiperResult<AudioStreamIterator<'_>> {
+        let streamer =
+            self.speech_streamer(phonemes, synthesis_config, chunk_size, chunk_padding)?;
+        Ok(Box::new(streamer))
+    }
+    fn stream_synthesis_cancellable(
+        &self,
+        phonemes: String,
+        synthesis_config: Option<&PiperSynthesisConfig>,
+        chunk_size: usize,
+        chunk_padding: usize,
+        cancellation: CancellationToken,
+    ) -> PiperResult<AudioStreamIterator<'_>> {
+        let synthesis_config = match synthesis_config {
+            Some(synthesis_config) => Cow::Borrowed(synthesis_config),
+            None => Cow::Owned(self.fallback_synth_config()),
+        };
+        let mut streamer =
+            self.speech_streamer(phonemes, &synthesis_config, chunk_size, chunk_padding)?;
+        streamer.cancellation = Some(cancellation);
+        Ok(Box::new(streamer))
+    }
+}
+
+/// Decodes silence to measure how many audio samples the decoder produces per mel frame.
+/// Returns `None` if the decoder's input shapes are not fixed enough to build the probe.
+fn measure_hop_length(decoder: &Decoder, decoder_info: &SessionInfo) -> PiperResult<Option<usize>> {
+    let static_dim = |input: &str, axis: usize| {
+        decoder_info
+            .input(input)
+            .and_then(|info| info.shape.get(axis))
+            .and_then(|dim| usize::try_from(*dim).ok())
+            .filter(|dim| *dim > 0)
+    };
+    let Some(channels) = static_dim("z", 1) else {
+        return Ok(None);
+    };
+    let z = Array3::<f32>::zeros((1, channels, HOP_LENGTH_PROBE_FRAMES)).into_dyn();
+    let y_mask = Array3::<f32>::ones((1, 1, HOP_LENGTH_PROBE_FRAMES)).into_dyn();
+    let g = match decoder_info.input("g") {
+        Some(_) => {
+            let Some(embedding_size) = static_dim("g", 1) else {
+                return Ok(None);
+            };
+            Array3::<f32>::zeros((1, embedding_size, 1)).into_dyn()
+        }
+        None => Array1::<f32>::from_iter([]).into_dyn(),
+    };
+    let inputs = decoder_inputs(
+        z.view(),
+        y_mask.view(),
+        g.view(),
+        decoder.float_types.inputs,
+    )?;
+    let outputs = decoder
+        .session
+        .run(SessionInputs::from(inputs.as_slice()))
+        .map_err(inference_error)?;
+    let num_samples = float_output(&outputs[0], decoder.float_types.outputs)?.len();
+    if num_samples == 0 || num_samples % HOP_LENGTH_PROBE_FRAMES != 0 {
+        return Err(PiperError::ModelMismatch(format!(
+            "The decoder produced {} samples for {} mel frames, which is not a whole hop length",
+            num_samples, HOP_LENGTH_PROBE_FRAMES
+        )));
     }
+    Ok(Some(num_samples / HOP_LENGTH_PROBE_FRAMES))
 }
 
 struct EncoderOutputs {
     z: Array<f32, Dim<IxDynImpl>>,
     y_mask: Array<f32, Dim<IxDynImpl>>,
-    #[allow(dead_code)]
     p_duration: Option<Array<f32, Dim<IxDynImpl>>>,
     g: Array<f32, Dim<IxDynImpl>>,
 }
 
 impl EncoderOutputs {
     #[inline(always)]
-    fn from_values(values: SessionOutputs) -> PiperResult<Self> {
-        let z = {
-            let z_t = match values["z"].try_extract_tensor::<f32>() {
-                Ok(out) => out,
-                Err(e) => {
-                    return Err(PiperError::OperationError(format!(
-                        "Failed to run model inference. Error: {}",
-                        e
-                    )))
-                }
-            };
-            z_t.view().clone().into_owned()
-        };
-        let y_mask = {
-            let y_mask_t = match values["y_mask"].try_extract_tensor::<f32>() {
-                Ok(out) => out,
-                Err(e) => {
-                    return Err(PiperError::OperationError(format!(
-                        "Failed to run model inference. Error: {}",
-                        e
-                    )))
-                }
-            };
-            y_mask_t.view().clone().into_owned()
-        };
-        let p_duration = if values.contains_key("p_duration") {
-            let p_duration_t = match values["p_duration"].try_extract_tensor::<f32>() {
-                Ok(out) => out,
-                Err(e) => {
-                    return Err(PiperError::OperationError(format!(
-                        "Failed to run model inference. Error: {}",
-                        e
-                    )))
-                }
-            };
-            Some(p_duration_t.view().clone().into_owned())
-        } else {
-            None
-        };
+    fn from_values(values: SessionOutputs, float_type: FloatType) -> PiperResult<Self> {
+        // Kept as f32 and converted back when fed to an fp16 decoder
+        let z = float_output(&values["z"], float_type)?.into_owned();
+        let y_mask = float_output(&values["y_mask"], float_type)?.into_owned();
+        let p_duration = extract_durations(&values, float_type)?;
         let g = if values.contains_key("g") {
-            let g_t = match values["g"].try_extract_tensor::<f32>() {
-                Ok(out) => out,
-                Err(e) => {
-                    return Err(PiperError::OperationError(format!(
-                        "Failed to run model inference. Error: {}",
-                        e
-                    )))
-                }
-            };
-            g_t.view().clone().into_owned()
+            float_output(&values["g"], float_type)?.into_owned()
         } else {
             Array1::<f32>::from_iter([]).into_dyn()
         };
@@ -703,48 +2140,135 @@ impl EncoderOutputs {
             g,
         })
     }
-    fn infer_decoder(&self, session: &Session) -> PiperResult<AudioSamples> {
-        let outputs = {
-            let mut inputs = vec![
-                SessionInputValue::from(Value::from_array(self.z.view()).unwrap()),
-                SessionInputValue::from(Value::from_array(self.y_mask.view()).unwrap()),
-            ];
-            if !self.g.is_empty() {
-                inputs.push(SessionInputValue::from(
-                    Value::from_array(self.g.view()).unwrap(),
-                ));
+    /// Replaces the speaker conditioning `g` of every batch item with the given embedding
+    fn set_speaker_embedding(&mut self, embedding: &SpeakerEmbedding) -> PiperResult<()> {
+        let batch_size = self.g.shape().first().copied().unwrap_or_default();
+        if self.g.is_empty() || embedding.values.len() * batch_size != self.g.len() {
+            return Err(PiperError::InvalidSpeaker(format!(
+                "The speaker embedding has {} values, but the model expects {}",
+                embedding.values.len(),
+                self.g.len() / batch_size.max(1)
+            )));
+        }
+        let values = Vec::from_iter((0..batch_size).flat_map(|_| embedding.values.iter().copied()));
+        self.g = match Array::from_shape_vec(self.g.raw_dim(), values) {
+            Ok(g) => g,
+            Err(e) => {
+                return Err(PiperError::TensorShape(format!(
+                    "Invalid speaker embedding shape. Error: {}",
+                    e
+                )))
             }
-            match session.run(SessionInputs::from(inputs.as_slice())) {
+        };
+        Ok(())
+    }
+    /// Splits the outputs of a batched encoder run into one item per sentence,
+    /// dropping the frames that `y_mask` marks as padding
+    fn split(&self, input_lengths: &[usize]) -> Vec<EncoderOutputs> {
+        Vec::from_iter(input_lengths.iter().enumerate().map(|(index, input_len)| {
+            let item = Slice::from(index..index + 1);
+            let num_frames = self.y_mask.index_axis(Axis(0), index).sum().round() as usize;
+            let frames = Slice::from(..num_frames);
+            let p_duration = self.p_duration.as_ref().map(|p_duration| {
+                let last_axis = Axis(p_duration.ndim() - 1);
+                let p_duration = p_duration.slice_axis(Axis(0), item);
+                let input_len = (*input_len).min(p_duration.len_of(last_axis));
+                p_duration
+                    .slice_axis(last_axis, Slice::from(..input_len))
+                    .to_owned()
+            });
+            let g = if self.g.is_empty() {
+                self.g.clone()
+            } else {
+                self.g.slice_axis(Axis(0), item).to_owned()
+            };
+            EncoderOutputs {
+                z: self
+                    .z
+                    .slice_axis(Axis(0), item)
+                    .slice_axis(Axis(2), frames)
+                    .to_owned(),
+                y_mask: self
+                    .y_mask
+                    .slice_axis(Axis(0), item)
+                    .slice_axis(Axis(2), frames)
+                    .to_owned(),
+                p_duration,
+                g,
+            }
+        }))
+    }
+    fn infer_decoder(&self, decoder: &Decoder) -> PiperResult<AudioSamples> {
+        let _span = tracing::debug_span!("decoder", num_frames = self.z.shape()[2]).entered();
+        let outputs = {
+            let inputs = decoder_inputs(
+                self.z.view(),
+                self.y_mask.view(),
+                self.g.view(),
+                decoder.float_types.inputs,
+            )?;
+            match decoder.session.run(SessionInputs::from(inputs.as_slice())) {
                 Ok(out) => out,
-                Err(e) => {
-                    return Err(PiperError::OperationError(format!(
-                        "Failed to run model inference. Error: {}",
-                        e
-                    )))
-                }
+                Err(e) => return Err(inference_error(e)),
             }
         };
-        match outputs[0].try_extract_tensor::<f32>() {
-            Ok(out) => Ok(Vec::from(out.view().as_slice().unwrap()).into()),
-            Err(e) => Err(PiperError::OperationError(format!(
-                "Failed to run model inference. Error: {}",
-                e
-            ))),
-        }
+        let audio = float_output(&outputs[0], decoder.float_types.outputs)?;
+        Ok(audio_tensor_to_vec(audio.view())?.into())
+    }
+}
+
+/// Inputs of a decoder run. `g` is left out if empty, i.e. for single-speaker models.
+fn decoder_inputs(
+    z: ArrayView<f32, Dim<IxDynImpl>>,
+    y_mask: ArrayView<f32, Dim<IxDynImpl>>,
+    g: ArrayView<f32, Dim<IxDynImpl>>,
+    float_type: FloatType,
+) -> PiperResult<Vec<SessionInputValue<'static>>> {
+    let input_error = |e: ort::Error| PiperError::Inference {
+        message: "Failed to create decoder inputs".to_string(),
+        source: e,
+    };
+    let mut inputs = vec![
+        float_input(z, float_type).map_err(input_error)?,
+        float_input(y_mask, float_type).map_err(input_error)?,
+    ];
+    if !g.is_empty() {
+        inputs.push(float_input(g, float_type).map_err(input_error)?);
+    }
+    Ok(inputs)
+}
+
+/// Copies the audio output of a model, which is expected to be contiguous
+fn audio_tensor_to_vec(audio: ArrayView<f32, Dim<IxDynImpl>>) -> PiperResult<Vec<f32>> {
+    match audio.as_slice() {
+        Some(samples) => Ok(samples.to_vec()),
+        None => Err(PiperError::TensorShape(format!(
+            "The audio output of the model is not contiguous, its shape is {:?}",
+            audio.shape()
+        ))),
     }
 }
 
 struct SpeechStreamer {
-    decoder_model: Arc<Session>,
+    decoder_model: Arc<Decoder>,
     encoder_outputs: EncoderOutputs,
+    /// Handed out with the first chunk
+    alignment: Option<Vec<PhonemeAlignment>>,
+    /// Handed out with the first chunk
+    timings: SynthesisTimings,
     mel_chunker: AdaptiveMelChunker,
     one_shot: bool,
+    /// Checked before decoding each chunk
+    cancellation: Option<CancellationToken>,
 }
 
 impl SpeechStreamer {
     fn new(
-        decoder_model: Arc<Session>,
+        decoder_model: Arc<Decoder>,
         encoder_outputs: EncoderOutputs,
+        alignment: Option<Vec<PhonemeAlignment>>,
+        chunker_config: MelChunkerConfig,
+        hop_length: usize,
         chunk_size: usize,
         chunk_padding: usize,
     ) -> Self {
@@ -753,13 +2277,18 @@ impl SpeechStreamer {
             num_frames as isize,
             chunk_size as isize,
             chunk_padding as isize,
+            chunker_config,
+            hop_length as isize,
         );
         let one_shot = num_frames <= (chunk_size * 2 + (chunk_padding * 2));
         Self {
             decoder_model,
             encoder_outputs,
+            alignment,
+            timings: SynthesisTimings::default(),
             mel_chunker,
             one_shot,
+            cancellation: None,
         }
     }
     fn synthesize_chunk(
@@ -768,33 +2297,25 @@ impl SpeechStreamer {
         audio_index: ndarray::Slice,
     ) -> PiperResult<AudioSamples> {
         // println!("Mel index: {:?}\nAudio Index: {:?}", mel_index, audio_index);
+        let _span = tracing::debug_span!("decoder_chunk", ?mel_index).entered();
         let audio = {
-            let session = Arc::clone(&self.decoder_model);
+            let decoder = Arc::clone(&self.decoder_model);
             let z_view = self.encoder_outputs.z.view();
             let y_mask_view = self.encoder_outputs.y_mask.view();
             let z_chunk = z_view.slice_axis(Axis(2), mel_index);
             let y_mask_chunk = y_mask_view.slice_axis(Axis(2), mel_index);
-            let mut inputs = vec![
-                SessionInputValue::from(Value::from_array(z_chunk).unwrap()),
-                SessionInputValue::from(Value::from_array(y_mask_chunk).unwrap()),
-            ];
-            if !self.encoder_outputs.g.is_empty() {
-                inputs.push(SessionInputValue::from(
-                    Value::from_array(self.encoder_outputs.g.view()).unwrap(),
-                ));
-            }
-            let outputs = session
+            let inputs = decoder_inputs(
+                z_chunk,
+                y_mask_chunk,
+                self.encoder_outputs.g.view(),
+                decoder.float_types.inputs,
+            )?;
+            let outputs = decoder
+                .session
                 .run(SessionInputs::from(inputs.as_slice()))
-                .map_err(|e| {
-                    PiperError::OperationError(format!(
-                        "Failed to run model inference. Error: {}",
-                        e
-                    ))
-                })?;
-            let audio_t = outputs[0].try_extract_tensor::<f32>().map_err(|e| {
-                PiperError::OperationError(format!("Failed to run model inference. Error: {}", e))
-            })?;
-            self.process_chunk_audio(audio_t.view().view(), audio_index)?
+                .map_err(inference_error)?;
+            let audio_t = float_output(&outputs[0], decoder.float_types.outputs)?;
+            self.process_chunk_audio(audio_t.view(), audio_index)?
         };
         Ok(audio)
     }
@@ -804,31 +2325,42 @@ impl SpeechStreamer {
         audio_view: ArrayView<f32, Dim<IxDynImpl>>,
         audio_index: ndarray::Slice,
     ) -> PiperResult<AudioSamples> {
-        let mut audio: AudioSamples = audio_view
-            .slice_axis(Axis(2), audio_index)
-            .as_slice()
-            .ok_or_else(|| PiperError::with_message("Invalid model audio output"))?
-            .to_vec()
-            .into();
+        let mut audio: AudioSamples =
+            audio_tensor_to_vec(audio_view.slice_axis(Axis(2), audio_index))?.into();
         audio.crossfade(42);
         Ok(audio)
     }
 }
 
 impl Iterator for SpeechStreamer {
-    type Item = PiperResult<AudioSamples>;
+    type Item = PiperResult<AudioChunk>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if self
+            .cancellation
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+        {
+            return None;
+        }
         let (mel_index, audio_index) = self.mel_chunker.next()?;
-        if self.one_shot {
+        let timer = Instant::now();
+        let samples = if self.one_shot {
             self.mel_chunker.consume();
-            Some(
-                self.encoder_outputs
-                    .infer_decoder(self.decoder_model.as_ref()),
-            )
+            self.encoder_outputs
+                .infer_decoder(self.decoder_model.as_ref())
         } else {
-            Some(self.synthesize_chunk(mel_index, audio_index))
-        }
+            self.synthesize_chunk(mel_index, audio_index)
+        };
+        let timings = SynthesisTimings {
+            decoder: Some(timer.elapsed()),
+            ..std::mem::take(&mut self.timings)
+        };
+        Some(samples.map(|samples| AudioChunk {
+            samples,
+            alignment: self.alignment.take(),
+            timings,
+        }))
     }
 }
 
@@ -836,16 +2368,28 @@ struct AdaptiveMelChunker {
     num_frames: isize,
     chunk_size: usize,
     chunk_padding: isize,
+    min_chunk_size: isize,
+    max_chunk_size: usize,
+    hop_length: isize,
     last_end_index: Option<isize>,
     step: usize,
 }
 
 impl AdaptiveMelChunker {
-    fn new(num_frames: isize, chunk_size: isize, chunk_padding: isize) -> Self {
+    fn new(
+        num_frames: isize,
+        chunk_size: isize,
+        chunk_padding: isize,
+        config: MelChunkerConfig,
+        hop_length: isize,
+    ) -> Self {
         Self {
             num_frames,
             chunk_size: chunk_size as usize,
             chunk_padding,
+            min_chunk_size: config.min_chunk_size as isize,
+            max_chunk_size: config.max_chunk_size,
+            hop_length,
             last_end_index: Some(0),
             step: 1,
         }
@@ -860,7 +2404,7 @@ impl Iterator for AdaptiveMelChunker {
 
     fn next(&mut self) -> Option<Self::Item> {
         let last_index = self.last_end_index?;
-        let chunk_size = (self.chunk_size * self.step).min(MAX_CHUNK_SIZE);
+        let chunk_size = (self.chunk_size * self.step).min(self.max_chunk_size);
         let (start_index, end_index): (isize, Option<isize>);
         let (start_padding, end_padding): (isize, Option<isize>);
         if last_index == 0 {
@@ -872,7 +2416,7 @@ impl Iterator for AdaptiveMelChunker {
         }
         let chunk_end = last_index + chunk_size as isize + self.chunk_padding;
         let remaining_frames = self.num_frames - chunk_end;
-        if remaining_frames <= MIN_CHUNK_SIZE {
+        if remaining_frames <= self.min_chunk_size {
             end_index = None;
             end_padding = None;
         } else {
@@ -882,7 +2426,509 @@ impl Iterator for AdaptiveMelChunker {
         self.step += 1;
         self.last_end_index = end_index;
         let chunk_index = ndarray::Slice::new(start_index, end_index, 1);
-        let audio_index = ndarray::Slice::new(start_padding * 256, end_padding.map(|i| i * 256), 1);
+        let audio_index = ndarray::Slice::new(
+            start_padding * self.hop_length,
+            end_padding.map(|i| i * self.hop_length),
+            1,
+        );
         Some((chunk_index, audio_index))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_clauses() {
+        assert_eq!(
+            split_clauses("wˈɛl, ðɛn; aɪ ɡˈɛs sˈoʊ."),
+            vec!["wˈɛl,", "ðɛn;", "aɪ ɡˈɛs sˈoʊ."]
+        );
+        // Breaks that are not followed by whitespace are part of a word
+        assert_eq!(split_clauses("1,000 ɐ:b"), vec!["1,000 ɐ:b"]);
+        assert_eq!(split_clauses("ɐ, "), vec!["ɐ,"]);
+        assert_eq!(split_clauses(""), vec![""]);
+    }
+
+    struct TestModel {
+        synth_config: RwLock<PiperSynthesisConfig>,
+        config: ModelConfig,
+        speaker_map: HashMap<i64, String>,
+        phoneme_id_config: PhonemeIdConfig,
+        text_normalizer: Option<Arc<dyn TextNormalizer>>,
+    }
+
+    impl VitsModelCommons for TestModel {
+        fn get_synth_config(&self) -> &RwLock<PiperSynthesisConfig> {
+            &self.synth_config
+        }
+        fn get_config(&self) -> &ModelConfig {
+            &self.config
+        }
+        fn get_speaker_map(&self) -> &HashMap<i64, String> {
+            &self.speaker_map
+        }
+        fn get_phoneme_id_config(&self) -> &PhonemeIdConfig {
+            &self.phoneme_id_config
+        }
+        fn get_text_normalizer(&self) -> Option<&dyn TextNormalizer> {
+            self.text_normalizer.as_deref()
+        }
+        fn hop_length(&self) -> usize {
+            HOP_LENGTH
+        }
+    }
+
+    const TEST_CONFIG: &[u8] = br#"{
+        "audio": { "sample_rate": 22050 },
+        "num_speakers": 2,
+        "speaker_id_map": { "a": 0, "b": 1 },
+        "espeak": { "voice": "en-us" },
+        "inference": { "noise_scale": 0.667, "length_scale": 1.0, "noise_w": 0.8 },
+        "num_symbols": 6,
+        "phoneme_id_map": { "_": [0], "^": [1], "$": [2], " ": [3], "a": [4], "b": [5] }
+    }"#;
+
+    fn test_model() -> TestModel {
+        let (config, synth_config) =
+            load_model_config_from_bytes(TEST_CONFIG).unwrap_or_else(|e| panic!("{}", e));
+        TestModel {
+            synth_config: RwLock::new(synth_config),
+            speaker_map: reversed_mapping(&config.speaker_id_map),
+            text_normalizer: NormalizerOption::default().resolve(&config_language(&config)),
+            config,
+            phoneme_id_config: PhonemeIdConfig::default(),
+        }
+    }
+
+    fn config_issues(config: &ModelConfig) -> Vec<ConfigIssue> {
+        match validate_model_config(config) {
+            Ok(()) => Vec::new(),
+            Err(PiperError::InvalidConfig(issues)) => issues,
+            Err(e) => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[test]
+    fn test_validate_model_config() {
+        let valid = || parse_model_config(TEST_CONFIG).unwrap().0;
+        assert_eq!(config_issues(&valid()), vec![]);
+
+        let mut config = valid();
+        config.phoneme_id_map.remove(&PAD);
+        config.phoneme_id_map.insert(BOS, vec![]);
+        config.phoneme_id_map.remove(&EOS);
+        assert_eq!(
+            config_issues(&config),
+            vec![
+                ConfigIssue::MissingSpecialSymbol(PAD),
+                ConfigIssue::MissingSpecialSymbol(BOS),
+                ConfigIssue::MissingSpecialSymbol(EOS),
+            ]
+        );
+
+        let mut config = valid();
+        config.phoneme_id_map.clear();
+        assert_eq!(
+            config_issues(&config),
+            vec![
+                ConfigIssue::EmptyPhonemeIdMap,
+                ConfigIssue::MissingSpecialSymbol(PAD),
+                ConfigIssue::MissingSpecialSymbol(BOS),
+                ConfigIssue::MissingSpecialSymbol(EOS),
+            ]
+        );
+
+        let mut config = valid();
+        config.num_speakers = 3;
+        assert_eq!(
+            config_issues(&config),
+            vec![ConfigIssue::SpeakerCountMismatch {
+                num_speakers: 3,
+                speaker_id_map_len: 2,
+            }]
+        );
+        let mut config = valid();
+        config.num_speakers = 1;
+        assert_eq!(
+            config_issues(&config),
+            vec![ConfigIssue::SpeakerCountMismatch {
+                num_speakers: 1,
+                speaker_id_map_len: 2,
+            }]
+        );
+
+        let mut config = valid();
+        config.speaker_id_map.insert("b".to_string(), 2);
+        config.speaker_id_map.insert("a".to_string(), -1);
+        assert_eq!(
+            config_issues(&config),
+            vec![
+                ConfigIssue::SpeakerIdOutOfRange {
+                    name: "a".to_string(),
+                    id: -1,
+                    num_speakers: 2,
+                },
+                ConfigIssue::SpeakerIdOutOfRange {
+                    name: "b".to_string(),
+                    id: 2,
+                    num_speakers: 2,
+                },
+            ]
+        );
+
+        let mut config = valid();
+        config.audio.sample_rate = 0;
+        config.audio.hop_length = Some(0);
+        assert_eq!(
+            config_issues(&config),
+            vec![ConfigIssue::ZeroSampleRate, ConfigIssue::ZeroHopLength]
+        );
+
+        let mut config = valid();
+        config.inference.noise_scale = -0.5;
+        config.inference.length_scale = 0.0;
+        config.inference.noise_w = f32::INFINITY;
+        assert_eq!(
+            config_issues(&config),
+            vec![
+                ConfigIssue::ScaleOutOfRange {
+                    name: "noise_scale",
+                    value: -0.5,
+                },
+                ConfigIssue::ScaleOutOfRange {
+                    name: "length_scale",
+                    value: 0.0,
+                },
+                ConfigIssue::ScaleOutOfRange {
+                    name: "noise_w",
+                    value: f32::INFINITY,
+                },
+            ]
+        );
+        // NaN is never equal to itself, so compare the names only
+        let mut config = valid();
+        config.inference.length_scale = f32::NAN;
+        assert!(matches!(
+            config_issues(&config).as_slice(),
+            [ConfigIssue::ScaleOutOfRange { name: "length_scale", value }] if value.is_nan()
+        ));
+    }
+
+    #[test]
+    fn test_poisoned_synth_config_is_recovered() {
+        let model = test_model();
+        let poisoner = std::thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    let _guard = model.synth_config.write();
+                    panic!("poison the synth config");
+                })
+                .join()
+        });
+        assert!(poisoner.is_err());
+        assert!(model.synth_config.is_poisoned());
+
+        assert!(VitsModelCommons::set_speaker(&model, 1).is_none());
+        let synth_config = model.fallback_synth_config();
+        assert_eq!(synth_config.speaker, Some(Speaker::Id(1)));
+        assert!(model.resolve_speaker_id(&synth_config).is_ok());
+    }
+
+    fn missing_file(result: PiperResult<Arc<dyn PiperModel + Send + Sync>>) -> PathBuf {
+        match result {
+            Err(PiperError::MissingFile { path, .. }) => path,
+            Err(e) => panic!("expected a missing file, got {}", e),
+            Ok(_) => panic!("expected a missing file, the model loaded"),
+        }
+    }
+
+    #[test]
+    fn test_model_builder() {
+        let dir = std::env::temp_dir().join(format!("piper-rs-builder-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config_path = dir.join("voice.onnx.json");
+        std::fs::write(&config_path, TEST_CONFIG).unwrap();
+        let streaming_config = String::from_utf8(TEST_CONFIG.to_vec()).unwrap().replacen(
+            '{',
+            r#"{ "streaming": true,"#,
+            1,
+        );
+        let streaming_config_path = dir.join("streaming.onnx.json");
+        std::fs::write(&streaming_config_path, streaming_config).unwrap();
+
+        let missing_config = dir.join("missing.onnx.json");
+        assert_eq!(
+            missing_file(PiperModelBuilder::new(&missing_config).build()),
+            missing_config
+        );
+        // Model files are looked up next to the config, unless given explicitly
+        assert_eq!(
+            missing_file(PiperModelBuilder::new(&config_path).build()),
+            dir.join("voice.onnx")
+        );
+        assert_eq!(
+            missing_file(
+                PiperModelBuilder::new(&config_path)
+                    .onnx_path(dir.join("other.onnx"))
+                    .build()
+            ),
+            dir.join("other.onnx")
+        );
+        assert_eq!(
+            missing_file(PiperModelBuilder::new(&streaming_config_path).build()),
+            dir.join("encoder.onnx")
+        );
+        assert_eq!(
+            missing_file(
+                PiperModelBuilder::new(&streaming_config_path)
+                    .encoder_path(dir.join("other_encoder.onnx"))
+                    .decoder_path(dir.join("other_decoder.onnx"))
+                    .build()
+            ),
+            dir.join("other_encoder.onnx")
+        );
+
+        // The files given must match the kind of model the config declares
+        let result = PiperModelBuilder::new(&config_path)
+            .encoder_path(dir.join("encoder.onnx"))
+            .decoder_path(dir.join("decoder.onnx"))
+            .build();
+        assert!(matches!(result, Err(PiperError::ModelMismatch(_))));
+        let result = PiperModelBuilder::new(&streaming_config_path)
+            .onnx_path(dir.join("voice.onnx"))
+            .build();
+        assert!(matches!(result, Err(PiperError::ModelMismatch(_))));
+        let result = PiperModelBuilder::new(&streaming_config_path)
+            .encoder_path(dir.join("encoder.onnx"))
+            .build();
+        assert!(matches!(result, Err(PiperError::OperationError(_))));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_model_builder_from_bytes() {
+        // Nothing can be looked up next to a config in memory
+        let result = PiperModelBuilder::from_config_bytes(TEST_CONFIG).build();
+        assert!(matches!(result, Err(PiperError::OperationError(_))));
+        let result = PiperModelBuilder::from_config_bytes(TEST_CONFIG)
+            .encoder_bytes(b"")
+            .decoder_bytes(b"")
+            .build();
+        assert!(matches!(result, Err(PiperError::ModelMismatch(_))));
+    }
+
+    #[test]
+    fn test_optimized_model_paths() {
+        let options = ModelOptions {
+            optimized_model_path: Some(PathBuf::from("cache/voice.onnx")),
+            ..Default::default()
+        };
+        assert_eq!(
+            options.optimized_model_path_for(None),
+            Some(PathBuf::from("cache/voice.onnx"))
+        );
+        assert_eq!(
+            options.optimized_model_path_for(Some("encoder")),
+            Some(PathBuf::from("cache/encoder.voice.onnx"))
+        );
+        assert_eq!(ModelOptions::default().optimized_model_path_for(None), None);
+    }
+
+    #[test]
+    fn test_text_to_codepoints() {
+        assert_eq!(
+            text_to_codepoints("Hello World. How are you?  Fine!"),
+            vec!["hello world.", "how are you?", "fine!"]
+        );
+        // Only breaks followed by whitespace end a sentence
+        assert_eq!(text_to_codepoints("Pi is 3.14..."), vec!["pi is 3.14..."]);
+        assert!(text_to_codepoints(" \n").is_empty());
+        // Accented letters become the base letter and a combining mark
+        assert_eq!(text_to_codepoints("Ça va"), vec!["c\u{327}a va"]);
+    }
+
+    #[test]
+    fn test_text_phonemes_bypass_espeak() {
+        let mut model = test_model();
+        model.config.phoneme_type = PhonemeType::Text;
+        // Phonemizing with eSpeak would fail on this voice
+        model.config.espeak.voice = "no-such-voice".to_string();
+        let phonemes = model.do_phonemize_text("Ab, ba. Bá!").unwrap();
+        assert_eq!(phonemes.to_vec(), vec!["ab, ba.", "ba\u{301}!"]);
+
+        // Characters missing from the phoneme id map are dropped and reported
+        let phoneme_ids = model.phonemes_to_input_ids("ba\u{301}!").unwrap();
+        assert_eq!(
+            Vec::from_iter(phoneme_ids.missing.into_keys()),
+            vec!['!', '\u{301}']
+        );
+        assert_eq!(phoneme_ids.ids, vec![1, 0, 5, 0, 4, 0, 2]);
+    }
+
+    #[test]
+    fn test_text_normalizer_is_per_voice() {
+        let mut english = test_model();
+        english.config.phoneme_type = PhonemeType::Text;
+        let mut plain = test_model();
+        plain.config.phoneme_type = PhonemeType::Text;
+        plain.text_normalizer = NormalizerOption::Disabled.resolve("en-us");
+        assert_eq!(
+            english.do_phonemize_text("5 km").unwrap().to_vec(),
+            vec!["five kilometers"]
+        );
+        assert_eq!(
+            plain.do_phonemize_text("5 km").unwrap().to_vec(),
+            vec!["5 km"]
+        );
+    }
+
+    #[test]
+    fn test_warm_up_phonemes_have_ids() {
+        let model = test_model();
+        assert_eq!(model.warm_up_phonemes(5), "ababa");
+        let phoneme_ids = model
+            .phonemes_to_input_ids(&model.warm_up_phonemes(8))
+            .unwrap();
+        assert!(phoneme_ids.missing.is_empty());
+    }
+
+    #[test]
+    fn test_phoneme_alignment() {
+        let model = test_model();
+        let phoneme_ids = [1, 0, 4, 0, 5, 0, 2];
+        // Negative durations count as zero, the last one is batch padding
+        let durations = Array1::from(vec![2.0, 1.0, 3.0, 0.0, -1.0, 2.5, 1.5, 9.0]);
+        let alignment = model.phoneme_alignment(&phoneme_ids, durations.view().into_dyn());
+        assert_eq!(alignment.len(), phoneme_ids.len());
+        assert_eq!(
+            Vec::from_iter(alignment.iter().map(|phoneme| phoneme.phoneme)),
+            vec![
+                Some('^'),
+                Some('_'),
+                Some('a'),
+                Some('_'),
+                Some('b'),
+                Some('_'),
+                Some('$')
+            ]
+        );
+        assert_eq!(alignment[0].start_ms, 0.0);
+        for (previous, next) in alignment.iter().zip(&alignment[1..]) {
+            assert_eq!(previous.end_ms, next.start_ms);
+        }
+        assert_eq!(alignment[4].start_ms, alignment[4].end_ms);
+        // The summed durations times the hop length are the length of the audio
+        let num_samples = 10.0 * HOP_LENGTH as f32;
+        let audio_ms = num_samples * 1000.0 / 22050.0;
+        assert!((alignment[6].end_ms - audio_ms).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_non_contiguous_audio_is_an_error() {
+        let audio = Array::from_shape_vec((1, 2, 3), vec![0.0f32; 6])
+            .unwrap()
+            .into_dyn();
+        assert!(audio_tensor_to_vec(audio.view()).is_ok());
+        let transposed = audio.t();
+        assert!(matches!(
+            audio_tensor_to_vec(transposed),
+            Err(PiperError::TensorShape(_))
+        ));
+    }
+
+    #[test]
+    fn test_trim_batch_audio_with_durations() {
+        // Two sentences padded to 4 phonemes, each phoneme lasting `durations` frames of 2 samples
+        let phoneme_batch = vec![vec![1, 4, 2], vec![1, 5, 4, 2]];
+        let durations =
+            Array::from_shape_vec((2, 1, 4), vec![1.0, 0.6, 1.0, 7.0, 1.0, 2.0, 0.0, 1.0])
+                .unwrap()
+                .into_dyn();
+        let lengths = batch_lengths_from_durations(durations.view(), &phoneme_batch, 2);
+        assert_eq!(lengths, vec![6, 8]);
+        // Sequential runs give the unpadded audio of each sentence
+        let sequential = vec![
+            vec![1.0, 1.0, 4.0, 4.0, 2.0, 2.0],
+            (1..=8).map(|i| i as f32).collect(),
+        ];
+        let mut padded = sequential[0].clone();
+        padded.resize(8, 0.0);
+        padded.extend(&sequential[1]);
+        let audio = Array::from_shape_vec((2, 1, 8), padded).unwrap().into_dyn();
+        assert_eq!(trim_batch_audio(audio.view(), &lengths), sequential);
+        // A sentence with no predicted frames still decodes one
+        let durations = Array::from_shape_vec((1, 1, 2), vec![0.0, -1.0])
+            .unwrap()
+            .into_dyn();
+        assert_eq!(
+            batch_lengths_from_durations(durations.view(), &[vec![1, 2]], 256),
+            vec![256]
+        );
+    }
+
+    #[test]
+    fn test_crossfade_clauses() {
+        // The tail is longer than the clause: its start plays first, its end fades into the clause
+        let tail = Vec::from_iter((10..20).map(|i| i as f32));
+        let mixed = crossfade_clauses(tail, vec![1.0, 2.0, 3.0]);
+        assert_eq!(mixed.len(), 10);
+        assert_eq!(mixed[..7], [10.0, 11.0, 12.0, 13.0, 14.0, 15.0, 16.0]);
+        let fade = |i: usize| (i as f32 + 0.5) / 3.0 * std::f32::consts::FRAC_PI_2;
+        for (i, (clause, previous)) in [(1.0, 17.0), (2.0, 18.0), (3.0, 19.0)]
+            .into_iter()
+            .enumerate()
+        {
+            let expected = clause * fade(i).sin() + previous * fade(i).cos();
+            assert!((mixed[7 + i] - expected).abs() < 1e-5);
+        }
+        // A short tail only overlaps the start of the clause
+        let mixed = crossfade_clauses(vec![1.0, 1.0], vec![0.0, 0.0, 4.0, 5.0]);
+        assert_eq!(mixed.len(), 4);
+        assert_eq!(mixed[2..], [4.0, 5.0]);
+        assert!(mixed[0] > mixed[1]);
+        assert_eq!(crossfade_clauses(vec![], vec![1.0]), vec![1.0]);
+    }
+
+    #[test]
+    fn test_mel_chunker_config_validation() {
+        assert!(MelChunkerConfig::default().validate().is_ok());
+        let config = |min_chunk_size, max_chunk_size| MelChunkerConfig {
+            min_chunk_size,
+            max_chunk_size,
+        };
+        assert!(config(0, 1).validate().is_ok());
+        assert!(config(8, 8).validate().is_ok());
+        assert!(matches!(
+            config(0, 0).validate(),
+            Err(PiperError::OperationError(_))
+        ));
+        assert!(matches!(
+            config(9, 8).validate(),
+            Err(PiperError::OperationError(_))
+        ));
+    }
+
+    #[test]
+    fn test_config_errors_keep_their_source() {
+        use std::error::Error;
+
+        let Err(error) = load_model_config_from_bytes(b"{ not json") else {
+            panic!("invalid JSON was accepted");
+        };
+        assert!(matches!(error, PiperError::ConfigParse { path: None, .. }));
+        assert!(error
+            .source()
+            .is_some_and(|source| source.is::<serde_json::Error>()));
+
+        let Err(error) = load_model_config(Path::new("/nonexistent/voice.onnx.json")) else {
+            panic!("a missing config was loaded");
+        };
+        assert!(matches!(error, PiperError::MissingFile { .. }));
+        assert!(error
+            .source()
+            .is_some_and(|source| source.is::<io::Error>()));
+    }
+}
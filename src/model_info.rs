@@ -0,0 +1,513 @@
+use ort::session::Session;
+use ort::value::ValueType;
+use std::collections::HashMap;
+
+pub use ort::tensor::TensorElementType;
+
+use crate::{ModelConfig, PiperError, PiperResult};
+
+const INPUT_NAMES: [&str; 3] = ["input", "input_lengths", "scales"];
+const SPEAKER_INPUT_NAME: &str = "sid";
+const ENCODER_OUTPUT_NAMES: [&str; 2] = ["z", "y_mask"];
+const SPEAKER_EMBEDDING_NAME: &str = "g";
+/// Optional output holding the number of valid audio samples of each batch item
+const OUTPUT_LENGTHS_NAME: &str = "output_lengths";
+/// Names of the optional output holding the duration of each phoneme id, in mel frames
+pub(crate) const DURATION_OUTPUT_NAMES: [&str; 2] = ["p_duration", "durations"];
+
+/// Name, element type and shape of a model input or output
+#[derive(Debug, Clone)]
+pub struct TensorInfo {
+    pub name: String,
+    /// `None` if the value is not a tensor
+    pub dtype: Option<TensorElementType>,
+    /// Dimensions of the tensor, `-1` marks a dynamic dimension
+    pub shape: Vec<i64>,
+}
+
+impl TensorInfo {
+    fn new(name: &str, value_type: &ValueType) -> Self {
+        Self {
+            name: name.to_string(),
+            dtype: value_type.tensor_type(),
+            shape: value_type.tensor_dimensions().cloned().unwrap_or_default(),
+        }
+    }
+}
+
+/// Metadata stored in an ONNX model
+#[derive(Debug, Clone, Default)]
+pub struct ModelMetadataInfo {
+    pub name: Option<String>,
+    pub producer: Option<String>,
+    pub description: Option<String>,
+    pub version: Option<i64>,
+    pub custom: HashMap<String, String>,
+}
+
+/// Element type of the float tensors exchanged with a model.
+///
+/// fp16 exports take and return half precision tensors, which are converted from and to f32.
+/// Quantized exports usually keep f32 inputs and outputs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FloatType {
+    #[default]
+    Float32,
+    Float16,
+}
+
+/// Float types of the inputs and of the outputs of one session
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FloatTypes {
+    pub inputs: FloatType,
+    pub outputs: FloatType,
+}
+
+/// Where the valid length of each item of a batched model output is read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BatchOutputLengths {
+    /// The `y_mask` output, counted in mel frames
+    FrameMask,
+    /// The `output_lengths` output, counted in samples
+    Samples,
+    /// The phoneme duration output, summed over the phonemes of each item
+    Durations,
+}
+
+/// Inputs, outputs and metadata of one onnxruntime session.
+///
+/// Non-streaming voices have a single session named `model`,
+/// streaming voices have an `encoder` and a `decoder` session.
+#[derive(Debug, Clone)]
+pub struct SessionInfo {
+    pub name: String,
+    pub inputs: Vec<TensorInfo>,
+    pub outputs: Vec<TensorInfo>,
+    pub metadata: ModelMetadataInfo,
+}
+
+impl SessionInfo {
+    pub(crate) fn from_session(name: &str, session: &Session) -> PiperResult<Self> {
+        let metadata = match session.metadata() {
+            Ok(metadata) => {
+                let mut custom = HashMap::new();
+                for key in metadata.custom_keys().unwrap_or_default() {
+                    if let Ok(Some(value)) = metadata.custom(&key) {
+                        custom.insert(key, value);
+                    }
+                }
+                ModelMetadataInfo {
+                    name: metadata.name().ok(),
+                    producer: metadata.producer().ok(),
+                    description: metadata.description().ok(),
+                    version: metadata.version().ok(),
+                    custom,
+                }
+            }
+            Err(e) => {
+                return Err(PiperError::Inference {
+                    message: format!("Failed to read metadata of the `{}` model", name),
+                    source: e,
+                })
+            }
+        };
+        Ok(Self {
+            name: name.to_string(),
+            inputs: Vec::from_iter(
+                session
+                    .inputs
+                    .iter()
+                    .map(|input| TensorInfo::new(&input.name, &input.input_type)),
+            ),
+            outputs: Vec::from_iter(
+                session
+                    .outputs
+                    .iter()
+                    .map(|output| TensorInfo::new(&output.name, &output.output_type)),
+            ),
+            metadata,
+        })
+    }
+    pub fn input(&self, name: &str) -> Option<&TensorInfo> {
+        self.inputs.iter().find(|input| input.name == name)
+    }
+    pub fn output(&self, name: &str) -> Option<&TensorInfo> {
+        self.outputs.iter().find(|output| output.name == name)
+    }
+    /// Float types of the session's inputs and outputs. Fails if a session mixes
+    /// f32 and f16 inputs, or outputs, or uses another float type.
+    pub fn float_types(&self) -> PiperResult<FloatTypes> {
+        Ok(FloatTypes {
+            inputs: common_float_type(&self.name, "input", &self.inputs)?,
+            outputs: common_float_type(&self.name, "output", &self.outputs)?,
+        })
+    }
+    /// Whether the model takes a speaker id. Some single-speaker exports do, and ignore it.
+    pub(crate) fn has_speaker_input(&self) -> bool {
+        self.input(SPEAKER_INPUT_NAME).is_some()
+    }
+    /// Whether the phoneme input accepts more than one sequence per run
+    pub fn supports_dynamic_batch(&self) -> bool {
+        self.input(INPUT_NAMES[0])
+            .and_then(|input| input.shape.first())
+            .is_some_and(|dim| *dim < 0)
+    }
+    /// How to trim the padded outputs of a batched run, `None` if batching is not possible
+    pub(crate) fn batch_output_lengths(&self) -> Option<BatchOutputLengths> {
+        if !self.supports_dynamic_batch() {
+            None
+        } else if self.output(OUTPUT_LENGTHS_NAME).is_some() {
+            Some(BatchOutputLengths::Samples)
+        } else if self.output(ENCODER_OUTPUT_NAMES[1]).is_some() {
+            Some(BatchOutputLengths::FrameMask)
+        } else if DURATION_OUTPUT_NAMES
+            .iter()
+            .any(|name| self.output(name).is_some())
+        {
+            Some(BatchOutputLengths::Durations)
+        } else {
+            None
+        }
+    }
+}
+
+/// Checks a non-streaming model against its config
+pub(crate) fn validate_vits_model(config: &ModelConfig, model: &SessionInfo) -> PiperResult<()> {
+    let mut problems = Vec::new();
+    check_text_inputs(config, model, &mut problems);
+    if model.outputs.is_empty() {
+        problems.push("the model has no outputs".to_string());
+    }
+    problems_to_result(problems)
+}
+
+/// Checks the encoder and decoder of a streaming model against its config
+pub(crate) fn validate_streaming_model(
+    config: &ModelConfig,
+    encoder: &SessionInfo,
+    decoder: &SessionInfo,
+) -> PiperResult<()> {
+    let mut problems = Vec::new();
+    check_text_inputs(config, encoder, &mut problems);
+    for name in ENCODER_OUTPUT_NAMES {
+        if encoder.output(name).is_none() {
+            problems.push(format!("the encoder has no `{}` output", name));
+        }
+        if decoder.input(name).is_none() {
+            problems.push(format!("the decoder has no `{}` input", name));
+        }
+    }
+    let has_speaker_embedding = encoder.output(SPEAKER_EMBEDDING_NAME).is_some();
+    if config.num_speakers > 1 && !has_speaker_embedding {
+        problems.push(format!(
+            "the config declares {} speakers, but the encoder has no `{}` output",
+            config.num_speakers, SPEAKER_EMBEDDING_NAME
+        ));
+    }
+    if has_speaker_embedding && decoder.input(SPEAKER_EMBEDDING_NAME).is_none() {
+        problems.push(format!(
+            "the encoder outputs `{}`, but the decoder has no `{}` input",
+            SPEAKER_EMBEDDING_NAME, SPEAKER_EMBEDDING_NAME
+        ));
+    }
+    if decoder.outputs.is_empty() {
+        problems.push("the decoder has no outputs".to_string());
+    }
+    problems_to_result(problems)
+}
+
+fn check_text_inputs(config: &ModelConfig, model: &SessionInfo, problems: &mut Vec<String>) {
+    for name in INPUT_NAMES {
+        if model.input(name).is_none() {
+            problems.push(format!(
+                "the `{}` model has no `{}` input",
+                model.name, name
+            ));
+        }
+    }
+    if let Some(input) = model.input(INPUT_NAMES[0]) {
+        if input.dtype != Some(TensorElementType::Int64) {
+            problems.push(format!(
+                "the `{}` input of the `{}` model should hold i64 phoneme ids",
+                input.name, model.name
+            ));
+        }
+    }
+    let has_speaker_input = model.has_speaker_input();
+    if config.num_speakers > 1 && !has_speaker_input {
+        problems.push(format!(
+            "the config declares {} speakers, but the `{}` model has no `{}` input",
+            config.num_speakers, model.name, SPEAKER_INPUT_NAME
+        ));
+    } else if config.num_speakers <= 1 && has_speaker_input {
+        tracing::warn!(
+            model = model.name,
+            "the model takes a `{}` input, but the config declares a single speaker. Speaker 0 is used",
+            SPEAKER_INPUT_NAME
+        );
+    }
+    // The size of the embedding table is checked by running the model once it is loaded
+    if let Some(max_id) = config.phoneme_id_map.values().flatten().max() {
+        if *max_id >= config.num_symbols as i64 {
+            problems.push(format!(
+                "the phoneme id map uses id {}, but the config declares only {} symbols",
+                max_id, config.num_symbols
+            ));
+        }
+    }
+}
+
+fn common_float_type(model: &str, kind: &str, tensors: &[TensorInfo]) -> PiperResult<FloatType> {
+    let mut common = None;
+    for tensor in tensors {
+        let float_type = match tensor.dtype {
+            Some(TensorElementType::Float32) => FloatType::Float32,
+            Some(TensorElementType::Float16) => FloatType::Float16,
+            Some(dtype @ (TensorElementType::Float64 | TensorElementType::Bfloat16)) => {
+                return Err(PiperError::ModelMismatch(format!(
+                    "The `{}` {} of the `{}` model holds {} values, only f32 and f16 are supported",
+                    tensor.name, kind, model, dtype
+                )))
+            }
+            Some(dtype @ (TensorElementType::Int8 | TensorElementType::Uint8)) => {
+                return Err(PiperError::ModelMismatch(format!(
+                    "The `{}` {} of the `{}` model holds quantized {} values, only f32 and f16 are supported. Quantize the voice with float inputs and outputs instead",
+                    tensor.name, kind, model, dtype
+                )))
+            }
+            _ => continue,
+        };
+        match common {
+            Some(common) if common != float_type => {
+                return Err(PiperError::ModelMismatch(format!(
+                    "The `{}` model mixes f32 and f16 {}s",
+                    model, kind
+                )))
+            }
+            _ => common = Some(float_type),
+        }
+    }
+    Ok(common.unwrap_or_default())
+}
+
+fn problems_to_result(problems: Vec<String>) -> PiperResult<()> {
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(PiperError::ModelMismatch(format!(
+            "The model does not match its config: {}",
+            problems.join("; ")
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tensor(name: &str, dtype: TensorElementType) -> TensorInfo {
+        TensorInfo {
+            name: name.to_string(),
+            dtype: Some(dtype),
+            shape: vec![-1],
+        }
+    }
+
+    fn session(inputs: Vec<TensorInfo>, outputs: Vec<TensorInfo>) -> SessionInfo {
+        SessionInfo {
+            name: "model".to_string(),
+            inputs,
+            outputs,
+            metadata: ModelMetadataInfo::default(),
+        }
+    }
+
+    fn config(num_speakers: u32, max_id: i64, num_symbols: u32) -> ModelConfig {
+        ModelConfig {
+            num_speakers,
+            num_symbols,
+            phoneme_id_map: HashMap::from([('_', vec![0]), ('a', vec![max_id])]),
+            ..Default::default()
+        }
+    }
+
+    fn text_inputs(speaker: bool) -> Vec<TensorInfo> {
+        let mut inputs = vec![
+            tensor("input", TensorElementType::Int64),
+            tensor("input_lengths", TensorElementType::Int64),
+            tensor("scales", TensorElementType::Float32),
+        ];
+        if speaker {
+            inputs.push(tensor("sid", TensorElementType::Int64));
+        }
+        inputs
+    }
+
+    fn tensors(names: &[&str]) -> Vec<TensorInfo> {
+        Vec::from_iter(
+            names
+                .iter()
+                .map(|name| tensor(name, TensorElementType::Float32)),
+        )
+    }
+
+    fn mismatch(result: PiperResult<()>) -> String {
+        match result {
+            Err(PiperError::ModelMismatch(message)) => message,
+            other => panic!("expected a model mismatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_validate_vits_model() {
+        let single = config(1, 5, 6);
+        let output = tensors(&["output"]);
+        let model = session(text_inputs(false), output.clone());
+        assert!(validate_vits_model(&single, &model).is_ok());
+        // Single-speaker exports may take a speaker id they ignore
+        let with_sid = session(text_inputs(true), output.clone());
+        assert!(validate_vits_model(&single, &with_sid).is_ok());
+
+        let mut inputs = text_inputs(false);
+        inputs.remove(1);
+        let message = mismatch(validate_vits_model(
+            &single,
+            &session(inputs, output.clone()),
+        ));
+        assert!(
+            message.contains("has no `input_lengths` input"),
+            "{}",
+            message
+        );
+
+        let mut inputs = text_inputs(false);
+        inputs[0].dtype = Some(TensorElementType::Int32);
+        let message = mismatch(validate_vits_model(
+            &single,
+            &session(inputs, output.clone()),
+        ));
+        assert!(
+            message.contains("should hold i64 phoneme ids"),
+            "{}",
+            message
+        );
+
+        let message = mismatch(validate_vits_model(&config(2, 5, 6), &model));
+        assert!(message.contains("declares 2 speakers"), "{}", message);
+
+        let message = mismatch(validate_vits_model(&config(1, 6, 6), &model));
+        assert!(message.contains("uses id 6"), "{}", message);
+
+        let no_outputs = session(text_inputs(false), vec![]);
+        let message = mismatch(validate_vits_model(&single, &no_outputs));
+        assert!(message.contains("has no outputs"), "{}", message);
+    }
+
+    #[test]
+    fn test_validate_streaming_model() {
+        let multi = config(2, 5, 6);
+        let encoder = session(text_inputs(true), tensors(&["z", "y_mask", "g"]));
+        let decoder = session(tensors(&["z", "y_mask", "g"]), tensors(&["output"]));
+        assert!(validate_streaming_model(&multi, &encoder, &decoder).is_ok());
+
+        let no_z = session(text_inputs(true), tensors(&["y_mask", "g"]));
+        let message = mismatch(validate_streaming_model(&multi, &no_z, &decoder));
+        assert!(
+            message.contains("the encoder has no `z` output"),
+            "{}",
+            message
+        );
+
+        let no_mask = session(tensors(&["z", "g"]), tensors(&["output"]));
+        let message = mismatch(validate_streaming_model(&multi, &encoder, &no_mask));
+        assert!(
+            message.contains("the decoder has no `y_mask` input"),
+            "{}",
+            message
+        );
+
+        let no_g = session(text_inputs(true), tensors(&["z", "y_mask"]));
+        let message = mismatch(validate_streaming_model(&multi, &no_g, &decoder));
+        assert!(
+            message.contains("the encoder has no `g` output"),
+            "{}",
+            message
+        );
+
+        let no_g_input = session(tensors(&["z", "y_mask"]), tensors(&["output"]));
+        let message = mismatch(validate_streaming_model(&multi, &encoder, &no_g_input));
+        assert!(
+            message.contains("the decoder has no `g` input"),
+            "{}",
+            message
+        );
+
+        let no_outputs = session(tensors(&["z", "y_mask", "g"]), vec![]);
+        let message = mismatch(validate_streaming_model(&multi, &encoder, &no_outputs));
+        assert!(
+            message.contains("the decoder has no outputs"),
+            "{}",
+            message
+        );
+
+        // Text inputs are checked on the encoder
+        let message = mismatch(validate_streaming_model(
+            &config(1, 9, 6),
+            &encoder,
+            &decoder,
+        ));
+        assert!(message.contains("uses id 9"), "{}", message);
+    }
+
+    #[test]
+    fn test_float_types() {
+        let fp16 = session(
+            vec![
+                tensor("input", TensorElementType::Int64),
+                tensor("scales", TensorElementType::Float16),
+            ],
+            vec![tensor("output", TensorElementType::Float16)],
+        );
+        assert_eq!(
+            fp16.float_types().unwrap(),
+            FloatTypes {
+                inputs: FloatType::Float16,
+                outputs: FloatType::Float16,
+            }
+        );
+        // fp16 weights with f32 inputs and outputs
+        let mixed_io = session(
+            vec![tensor("scales", TensorElementType::Float32)],
+            vec![tensor("output", TensorElementType::Float16)],
+        );
+        assert_eq!(mixed_io.float_types().unwrap().inputs, FloatType::Float32);
+
+        let mixed_outputs = session(
+            vec![],
+            vec![
+                tensor("z", TensorElementType::Float16),
+                tensor("y_mask", TensorElementType::Float32),
+            ],
+        );
+        assert!(matches!(
+            mixed_outputs.float_types(),
+            Err(PiperError::ModelMismatch(_))
+        ));
+        let bf16 = session(vec![tensor("scales", TensorElementType::Bfloat16)], vec![]);
+        assert!(matches!(
+            bf16.float_types(),
+            Err(PiperError::ModelMismatch(_))
+        ));
+        // Quantized tensors would be read as floats, ids and lengths stay valid inputs
+        for dtype in [TensorElementType::Int8, TensorElementType::Uint8] {
+            let quantized = session(
+                vec![tensor("input", TensorElementType::Int64)],
+                vec![tensor("output", dtype)],
+            );
+            assert!(matches!(
+                quantized.float_types(),
+                Err(PiperError::ModelMismatch(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,1120 @@
+use std::fmt;
+use std::sync::Arc;
+
+/// Rewrites text into the words a voice should speak before it is phonemized,
+/// e.g. numbers, amounts, dates and abbreviations
+pub trait TextNormalizer: Send + Sync {
+    fn normalize(&self, text: &str) -> String;
+}
+
+/// Which normalizer a voice runs on text before phonemizing it
+#[derive(Clone, Default)]
+pub enum NormalizerOption {
+    /// The built-in normalizer of the voice's language, if there is one
+    #[default]
+    Language,
+    /// A normalizer of your own, e.g. for a language without built-in rules
+    Custom(Arc<dyn TextNormalizer>),
+    /// Phonemizes text as written
+    Disabled,
+}
+
+impl NormalizerOption {
+    /// The normalizer of a voice speaking `language`
+    pub fn resolve(&self, language: &str) -> Option<Arc<dyn TextNormalizer>> {
+        match self {
+            Self::Language => normalizer_for(language),
+            Self::Custom(normalizer) => Some(normalizer.clone()),
+            Self::Disabled => None,
+        }
+    }
+}
+
+impl fmt::Debug for NormalizerOption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Language => f.write_str("Language"),
+            Self::Custom(_) => f.write_str("Custom(..)"),
+            Self::Disabled => f.write_str("Disabled"),
+        }
+    }
+}
+
+/// Built-in normalizer for a language code (`en_US`) or an eSpeak voice (`en-us`),
+/// chosen by the code without its region
+pub fn normalizer_for(language: &str) -> Option<Arc<dyn TextNormalizer>> {
+    let language = language.trim().to_lowercase();
+    let primary = language.split(['_', '-']).next().unwrap_or_default();
+    match primary {
+        "en" => Some(Arc::new(EnglishNormalizer)),
+        "de" => Some(Arc::new(GermanNormalizer)),
+        _ => None,
+    }
+}
+
+pub struct EnglishNormalizer;
+
+impl TextNormalizer for EnglishNormalizer {
+    fn normalize(&self, text: &str) -> String {
+        normalize_with(&ENGLISH, text)
+    }
+}
+
+pub struct GermanNormalizer;
+
+impl TextNormalizer for GermanNormalizer {
+    fn normalize(&self, text: &str) -> String {
+        normalize_with(&GERMAN, text)
+    }
+}
+
+/// Spelling and vocabulary of a language
+struct Rules {
+    thousands_separator: char,
+    decimal_separator: char,
+    decimal_point: &'static str,
+    minus: &'static str,
+    cardinal: fn(u64) -> String,
+    /// Ordinal of a number given the word before it, `None` if it has none
+    ordinal: fn(u64, Option<&str>) -> Option<String>,
+    year: fn(u64) -> String,
+    /// Hours, minutes and the spoken word that followed the time
+    time: fn(u64, u64, &str) -> String,
+    /// Day, month, optional year and the word before the date
+    date: fn(u64, usize, Option<u64>, Option<&str>) -> String,
+    /// Separators of numeric dates and the order of their fields
+    date_formats: &'static [(char, DateOrder)],
+    /// Suffixes of ordinals such as `1st`
+    ordinal_suffixes: &'static [&'static str],
+    /// Whether a number with a period before a month name, e.g. `5. März`, is an ordinal
+    dotted_ordinals: bool,
+    months: [&'static str; 12],
+    /// Lowercase words that may follow a time, and how they are spoken
+    time_words: &'static [(&'static str, &'static str)],
+    abbreviations: &'static [(&'static str, &'static str)],
+    /// Symbol, the words for exactly one, and the words after other amounts
+    units: &'static [(&'static str, &'static str, &'static str)],
+    currencies: &'static [Currency],
+    /// Joins the main and the minor unit of an amount
+    currency_joiner: &'static str,
+    /// How the symbols of URLs and email addresses are spoken
+    symbols: &'static [(char, &'static str)],
+}
+
+struct Currency {
+    symbols: &'static [&'static str],
+    one: &'static str,
+    many: &'static str,
+    minor_one: &'static str,
+    minor_many: &'static str,
+}
+
+#[derive(Clone, Copy)]
+enum DateOrder {
+    Ymd,
+    Mdy,
+    Dmy,
+}
+
+const LEADING_PUNCTUATION: [char; 10] = ['(', '[', '{', '"', '\'', '«', '„', '“', '¿', '¡'];
+const TRAILING_PUNCTUATION: [char; 14] = [
+    '.', ',', ';', ':', '!', '?', ')', ']', '}', '"', '\'', '»', '“', '”',
+];
+
+/// A whitespace separated word split into its surrounding punctuation and its core
+struct Word<'a> {
+    text: &'a str,
+    lead: &'a str,
+    core: &'a str,
+    trail: &'a str,
+}
+
+impl<'a> Word<'a> {
+    fn new(text: &'a str) -> Self {
+        let core_start = text
+            .find(|c| !LEADING_PUNCTUATION.contains(&c))
+            .unwrap_or(text.len());
+        let (lead, rest) = text.split_at(core_start);
+        let core_end = rest
+            .char_indices()
+            .rev()
+            .find(|(_, c)| !TRAILING_PUNCTUATION.contains(c))
+            .map_or(0, |(index, c)| index + c.len_utf8());
+        let (core, trail) = rest.split_at(core_end);
+        Self {
+            text,
+            lead,
+            core,
+            trail,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Number {
+    negative: bool,
+    /// Digits without separators
+    integer: String,
+    fraction: Option<String>,
+    /// Written without sign, separators or fraction, so it may be a year
+    plain: bool,
+}
+
+fn normalize_with(rules: &Rules, text: &str) -> String {
+    // Whitespace before each word is kept, so are line breaks
+    let mut separators = Vec::new();
+    let mut words = Vec::new();
+    let mut rest = text;
+    loop {
+        let start = rest
+            .find(|c: char| !c.is_whitespace())
+            .unwrap_or(rest.len());
+        let (separator, tail) = rest.split_at(start);
+        separators.push(separator);
+        if tail.is_empty() {
+            break;
+        }
+        let end = tail.find(char::is_whitespace).unwrap_or(tail.len());
+        words.push(Word::new(&tail[..end]));
+        rest = &tail[end..];
+    }
+    let mut normalized = String::with_capacity(text.len());
+    let mut index = 0;
+    while index < words.len() {
+        normalized.push_str(separators[index]);
+        let previous = index.checked_sub(1).map(|previous| words[previous].core);
+        match read_word(rules, &words[index..], previous) {
+            Some((spoken, trail, num_words)) => {
+                normalized.push_str(words[index].lead);
+                normalized.push_str(&spoken);
+                normalized.push_str(trail);
+                index += num_words;
+            }
+            None => {
+                normalized.push_str(words[index].text);
+                index += 1;
+            }
+        }
+    }
+    normalized.push_str(separators[separators.len() - 1]);
+    normalized
+}
+
+/// Spoken form of the first word, and of the next word if it completes it,
+/// with the trailing punctuation to keep and the number of words read.
+/// `None` if the word is spoken as written.
+fn read_word<'a>(
+    rules: &Rules,
+    words: &[Word<'a>],
+    previous: Option<&str>,
+) -> Option<(String, &'a str, usize)> {
+    let word = &words[0];
+    let core = word.core;
+    if core.is_empty() {
+        return None;
+    }
+    // The next word, if nothing separates it from this one
+    let next = words
+        .get(1)
+        .filter(|next| word.trail.is_empty() && next.lead.is_empty());
+
+    if let Some(trail) = word.trail.strip_prefix('.') {
+        let expansion = rules
+            .abbreviations
+            .iter()
+            .find(|(abbreviation, _)| abbreviation.strip_suffix('.') == Some(core));
+        if let Some((_, expansion)) = expansion {
+            // Keep the period of an abbreviation that ends the text
+            let trail = if words.len() == 1 { word.trail } else { trail };
+            return Some((expansion.to_string(), trail, 1));
+        }
+    }
+    if let Some(spoken) = read_address(rules, core) {
+        return Some((spoken, word.trail, 1));
+    }
+    if let Some((hours, minutes, suffix)) = parse_time(rules, core) {
+        return Some(
+            match (suffix, next.and_then(|next| time_word(rules, next.core))) {
+                (Some(suffix), _) => ((rules.time)(hours, minutes, suffix), word.trail, 1),
+                (None, Some(suffix)) => {
+                    let next = &words[1];
+                    ((rules.time)(hours, minutes, suffix), next.trail, 2)
+                }
+                (None, None) => ((rules.time)(hours, minutes, ""), word.trail, 1),
+            },
+        );
+    }
+    if let Some((day, month, year, trail)) = parse_date(rules, core, word.trail) {
+        return Some(((rules.date)(day, month, year, previous), trail, 1));
+    }
+    if let Some(ordinal) = read_ordinal(rules, word, words.get(1), previous) {
+        return Some(ordinal);
+    }
+    // Amounts and measures with the symbol attached, e.g. `$5`, `5€` or `50%`
+    for currency in rules.currencies {
+        for symbol in currency.symbols {
+            let amount = core
+                .strip_prefix(symbol)
+                .or_else(|| core.strip_suffix(symbol));
+            if let Some(number) = amount.and_then(|amount| parse_number(rules, amount)) {
+                return Some((read_amount(rules, currency, &number), word.trail, 1));
+            }
+        }
+    }
+    for (symbol, one, many) in rules.units {
+        let quantity = core.strip_suffix(symbol);
+        if let Some(number) = quantity.and_then(|quantity| parse_number(rules, quantity)) {
+            return Some((read_quantity(rules, &number, one, many), word.trail, 1));
+        }
+    }
+    let number = parse_number(rules, core)?;
+    // Amounts and measures with the symbol in the next word, e.g. `5 €` or `5 km`
+    if let Some(next) = next {
+        let currency = rules
+            .currencies
+            .iter()
+            .find(|currency| currency.symbols.contains(&next.core));
+        if let Some(currency) = currency {
+            return Some((read_amount(rules, currency, &number), next.trail, 2));
+        }
+        if let Some((_, one, many)) = rules.units.iter().find(|(symbol, ..)| *symbol == next.core) {
+            return Some((read_quantity(rules, &number, one, many), next.trail, 2));
+        }
+    }
+    Some((read_number(rules, &number, true), word.trail, 1))
+}
+
+/// Reads URLs and email addresses symbol by symbol
+fn read_address(rules: &Rules, core: &str) -> Option<String> {
+    let address = match core.split_once('@') {
+        Some((local, domain)) => {
+            let (name, top_level) = domain.rsplit_once('.')?;
+            if local.is_empty() || name.is_empty() || top_level.is_empty() {
+                return None;
+            }
+            core
+        }
+        None => match core
+            .strip_prefix("https://")
+            .or_else(|| core.strip_prefix("http://"))
+        {
+            Some(address) => address,
+            None if core.starts_with("www.") => core,
+            None => return None,
+        },
+    };
+    let mut spoken = Vec::new();
+    let mut part = String::new();
+    for c in address.trim_end_matches('/').chars() {
+        match rules.symbols.iter().find(|(symbol, _)| *symbol == c) {
+            Some((_, name)) => {
+                if !part.is_empty() {
+                    spoken.push(std::mem::take(&mut part));
+                }
+                spoken.push(name.to_string());
+            }
+            None => part.push(c),
+        }
+    }
+    if !part.is_empty() {
+        spoken.push(part);
+    }
+    Some(spoken.join(" "))
+}
+
+/// Hours, minutes and an attached suffix of `10:30` or `10:30pm`
+fn parse_time(rules: &Rules, core: &str) -> Option<(u64, u64, Option<&'static str>)> {
+    let (hours, rest) = core.split_once(':')?;
+    let minutes_end = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+    let (minutes, suffix) = rest.split_at(minutes_end);
+    let suffix = match suffix {
+        "" => None,
+        suffix => Some(time_word(rules, suffix)?),
+    };
+    if !(1..=2).contains(&hours.len())
+        || !hours.chars().all(|c| c.is_ascii_digit())
+        || minutes.len() != 2
+    {
+        return None;
+    }
+    let hours = hours.parse().ok()?;
+    let minutes = minutes.parse().ok()?;
+    (hours < 24 && minutes < 60).then_some((hours, minutes, suffix))
+}
+
+fn time_word(rules: &Rules, word: &str) -> Option<&'static str> {
+    let word = word.to_lowercase();
+    rules
+        .time_words
+        .iter()
+        .find(|(time_word, _)| *time_word == word)
+        .map(|(_, spoken)| *spoken)
+}
+
+/// Day, month, year and the trailing punctuation left of a numeric date
+fn parse_date<'a>(
+    rules: &Rules,
+    core: &str,
+    trail: &'a str,
+) -> Option<(u64, usize, Option<u64>, &'a str)> {
+    for (separator, order) in rules.date_formats {
+        let parts = Vec::from_iter(core.split(*separator));
+        if parts
+            .iter()
+            .any(|part| part.is_empty() || !part.chars().all(|c| c.is_ascii_digit()))
+        {
+            continue;
+        }
+        let (year, month, day, trail) = match (order, parts.as_slice()) {
+            (DateOrder::Ymd, [year, month, day]) if year.len() == 4 => {
+                (Some(year), month, day, trail)
+            }
+            (DateOrder::Mdy, [month, day, year]) | (DateOrder::Dmy, [day, month, year])
+                if year.len() == 4 =>
+            {
+                (Some(year), month, day, trail)
+            }
+            // `05.03.` without a year
+            (DateOrder::Dmy, [day, month]) if *separator == '.' && trail.starts_with('.') => {
+                (None, month, day, &trail[1..])
+            }
+            _ => continue,
+        };
+        if month.len() > 2 || day.len() > 2 {
+            continue;
+        }
+        let month = month
+            .parse::<usize>()
+            .ok()
+            .filter(|month| (1..=12).contains(month));
+        let day = day.parse::<u64>().ok().filter(|day| (1..=31).contains(day));
+        if let (Some(month), Some(day)) = (month, day) {
+            let year = year.and_then(|year| year.parse().ok());
+            return Some((day, month, year, trail));
+        }
+    }
+    None
+}
+
+/// Reads `21st`, or `5.` before a month name where ordinals are written with a period
+fn read_ordinal<'a>(
+    rules: &Rules,
+    word: &Word<'a>,
+    next: Option<&Word>,
+    previous: Option<&str>,
+) -> Option<(String, &'a str, usize)> {
+    let digits_end = word
+        .core
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(word.core.len());
+    let (digits, suffix) = word.core.split_at(digits_end);
+    if digits.is_empty() || (digits.len() > 1 && digits.starts_with('0')) {
+        return None;
+    }
+    let trail = if rules
+        .ordinal_suffixes
+        .contains(&suffix.to_lowercase().as_str())
+    {
+        word.trail
+    } else {
+        let before_month = next.is_some_and(|next| rules.months.contains(&next.core));
+        match word.trail.strip_prefix('.') {
+            Some(trail) if rules.dotted_ordinals && suffix.is_empty() && before_month => trail,
+            _ => return None,
+        }
+    };
+    let ordinal = (rules.ordinal)(digits.parse().ok()?, previous)?;
+    Some((ordinal, trail, 1))
+}
+
+fn parse_number(rules: &Rules, text: &str) -> Option<Number> {
+    let (negative, unsigned) = match text.strip_prefix(['-', '−']) {
+        Some(unsigned) => (true, unsigned),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let (integer, fraction) = match unsigned.split_once(rules.decimal_separator) {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (unsigned, None),
+    };
+    if fraction.is_some_and(|fraction| fraction.is_empty() || !is_digits(fraction)) {
+        return None;
+    }
+    let groups = Vec::from_iter(integer.split(rules.thousands_separator));
+    let grouped = groups.len() > 1;
+    if !groups.iter().all(|group| is_digits(group))
+        || (grouped && (groups[0].len() > 3 || groups[1..].iter().any(|group| group.len() != 3)))
+    {
+        return None;
+    }
+    Some(Number {
+        negative,
+        integer: groups.concat(),
+        fraction: fraction.map(str::to_string),
+        plain: unsigned.len() == text.len() && !grouped && fraction.is_none(),
+    })
+}
+
+fn is_digits(text: &str) -> bool {
+    !text.is_empty() && text.chars().all(|c| c.is_ascii_digit())
+}
+
+fn read_number(rules: &Rules, number: &Number, may_be_year: bool) -> String {
+    let mut words = Vec::new();
+    if number.negative {
+        words.push(rules.minus.to_string());
+    }
+    let digits = &number.integer;
+    match digits.parse::<u64>() {
+        Ok(value) if digits.len() == 1 || !digits.starts_with('0') => {
+            if may_be_year && number.plain && digits.len() == 4 {
+                words.push((rules.year)(value));
+            } else {
+                words.push((rules.cardinal)(value));
+            }
+        }
+        // Leading zeros and very long numbers are read digit by digit
+        _ => words.extend(read_digits(rules, digits)),
+    }
+    if let Some(ref fraction) = number.fraction {
+        words.push(rules.decimal_point.to_string());
+        words.extend(read_digits(rules, fraction));
+    }
+    words.join(" ")
+}
+
+fn read_digits<'a>(rules: &'a Rules, digits: &'a str) -> impl Iterator<Item = String> + 'a {
+    digits
+        .chars()
+        .filter_map(|digit| digit.to_digit(10))
+        .map(|digit| (rules.cardinal)(digit as u64))
+}
+
+fn read_quantity(rules: &Rules, number: &Number, one: &str, many: &str) -> String {
+    if number.integer == "1" && number.fraction.is_none() {
+        match number.negative {
+            true => format!("{} {}", rules.minus, one),
+            false => one.to_string(),
+        }
+    } else {
+        format!("{} {}", read_number(rules, number, false), many)
+    }
+}
+
+fn read_amount(rules: &Rules, currency: &Currency, number: &Number) -> String {
+    let minor = match number.fraction {
+        None => 0,
+        Some(ref fraction) if fraction.len() <= 2 => {
+            format!("{:0<2}", fraction).parse::<u64>().unwrap_or(0)
+        }
+        // e.g. fuel prices
+        Some(_) => return read_quantity(rules, number, currency.one, currency.many),
+    };
+    let main = Number {
+        fraction: None,
+        ..number.clone()
+    };
+    if minor == 0 {
+        return read_quantity(rules, &main, currency.one, currency.many);
+    }
+    let minor = Number {
+        negative: number.negative && main.integer.trim_start_matches('0').is_empty(),
+        integer: minor.to_string(),
+        fraction: None,
+        plain: false,
+    };
+    let minor = read_quantity(rules, &minor, currency.minor_one, currency.minor_many);
+    if main.integer.trim_start_matches('0').is_empty() {
+        return minor;
+    }
+    format!(
+        "{}{}{}",
+        read_quantity(rules, &main, currency.one, currency.many),
+        rules.currency_joiner,
+        minor
+    )
+}
+
+const ENGLISH_ONES: [&str; 20] = [
+    "zero",
+    "one",
+    "two",
+    "three",
+    "four",
+    "five",
+    "six",
+    "seven",
+    "eight",
+    "nine",
+    "ten",
+    "eleven",
+    "twelve",
+    "thirteen",
+    "fourteen",
+    "fifteen",
+    "sixteen",
+    "seventeen",
+    "eighteen",
+    "nineteen",
+];
+const ENGLISH_TENS: [&str; 10] = [
+    "", "", "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety",
+];
+const ENGLISH_SCALES: [(u64, &str); 6] = [
+    (1_000_000_000_000_000_000, "quintillion"),
+    (1_000_000_000_000_000, "quadrillion"),
+    (1_000_000_000_000, "trillion"),
+    (1_000_000_000, "billion"),
+    (1_000_000, "million"),
+    (1_000, "thousand"),
+];
+const ENGLISH_MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+fn english_cardinal(n: u64) -> String {
+    if let Some((scale, name)) = ENGLISH_SCALES.iter().find(|(scale, _)| n >= *scale) {
+        let mut spoken = format!("{} {}", english_cardinal(n / scale), name);
+        let rest = n % scale;
+        if rest != 0 {
+            spoken.push(' ');
+            spoken.push_str(&english_cardinal(rest));
+        }
+        return spoken;
+    }
+    match n {
+        0..=19 => ENGLISH_ONES[n as usize].to_string(),
+        20..=99 => match n % 10 {
+            0 => ENGLISH_TENS[(n / 10) as usize].to_string(),
+            ones => format!(
+                "{}-{}",
+                ENGLISH_TENS[(n / 10) as usize],
+                ENGLISH_ONES[ones as usize]
+            ),
+        },
+        _ => match n % 100 {
+            0 => format!("{} hundred", ENGLISH_ONES[(n / 100) as usize]),
+            rest => format!(
+                "{} hundred {}",
+                ENGLISH_ONES[(n / 100) as usize],
+                english_cardinal(rest)
+            ),
+        },
+    }
+}
+
+fn english_ordinal(n: u64, _previous: Option<&str>) -> Option<String> {
+    let cardinal = english_cardinal(n);
+    let (head, last) = cardinal.split_at(cardinal.rfind([' ', '-']).map_or(0, |index| index + 1));
+    let last = match last {
+        "one" => "first".to_string(),
+        "two" => "second".to_string(),
+        "three" => "third".to_string(),
+        "five" => "fifth".to_string(),
+        "eight" => "eighth".to_string(),
+        "nine" => "ninth".to_string(),
+        "twelve" => "twelfth".to_string(),
+        _ => match last.strip_suffix('y') {
+            Some(stem) => format!("{}ieth", stem),
+            None => format!("{}th", last),
+        },
+    };
+    Some(format!("{}{}", head, last))
+}
+
+/// Reads `1990` as `nineteen ninety` and `2005` as `two thousand five`
+fn english_year(n: u64) -> String {
+    match n {
+        2000..=2009 => english_cardinal(n),
+        1100..=2099 => match n % 100 {
+            0 => format!("{} hundred", english_cardinal(n / 100)),
+            rest @ 1..=9 => format!(
+                "{} oh {}",
+                english_cardinal(n / 100),
+                english_cardinal(rest)
+            ),
+            rest => format!("{} {}", english_cardinal(n / 100), english_cardinal(rest)),
+        },
+        _ => english_cardinal(n),
+    }
+}
+
+fn english_time(hours: u64, minutes: u64, suffix: &str) -> String {
+    let hours = english_cardinal(hours);
+    let mut spoken = match minutes {
+        0 if suffix.is_empty() => format!("{} o'clock", hours),
+        0 => hours,
+        1..=9 => format!("{} oh {}", hours, english_cardinal(minutes)),
+        _ => format!("{} {}", hours, english_cardinal(minutes)),
+    };
+    if !suffix.is_empty() {
+        spoken.push(' ');
+        spoken.push_str(suffix);
+    }
+    spoken
+}
+
+fn english_date(day: u64, month: usize, year: Option<u64>, previous: Option<&str>) -> String {
+    let mut spoken = format!(
+        "{} {}",
+        ENGLISH_MONTHS[month - 1],
+        english_ordinal(day, previous).unwrap_or_default()
+    );
+    if let Some(year) = year {
+        spoken.push_str(", ");
+        spoken.push_str(&english_year(year));
+    }
+    spoken
+}
+
+static ENGLISH: Rules = Rules {
+    thousands_separator: ',',
+    decimal_separator: '.',
+    decimal_point: "point",
+    minus: "minus",
+    cardinal: english_cardinal,
+    ordinal: english_ordinal,
+    year: english_year,
+    time: english_time,
+    date: english_date,
+    date_formats: &[('-', DateOrder::Ymd), ('/', DateOrder::Mdy)],
+    ordinal_suffixes: &["st", "nd", "rd", "th"],
+    dotted_ordinals: false,
+    months: ENGLISH_MONTHS,
+    time_words: &[("am", "a m"), ("a.m", "a m"), ("pm", "p m"), ("p.m", "p m")],
+    abbreviations: &[
+        ("Dr.", "Doctor"),
+        ("Mr.", "Mister"),
+        ("Mrs.", "Missus"),
+        ("Ms.", "Miz"),
+        ("Prof.", "Professor"),
+        ("Jr.", "Junior"),
+        ("Sr.", "Senior"),
+        ("e.g.", "for example"),
+        ("i.e.", "that is"),
+        ("etc.", "et cetera"),
+        ("vs.", "versus"),
+        ("approx.", "approximately"),
+    ],
+    units: &[
+        ("km/h", "one kilometer per hour", "kilometers per hour"),
+        ("mph", "one mile per hour", "miles per hour"),
+        ("km", "one kilometer", "kilometers"),
+        ("cm", "one centimeter", "centimeters"),
+        ("mm", "one millimeter", "millimeters"),
+        ("m", "one meter", "meters"),
+        ("mi", "one mile", "miles"),
+        ("ft", "one foot", "feet"),
+        ("kg", "one kilogram", "kilograms"),
+        ("mg", "one milligram", "milligrams"),
+        ("g", "one gram", "grams"),
+        ("lbs", "one pound", "pounds"),
+        ("lb", "one pound", "pounds"),
+        ("oz", "one ounce", "ounces"),
+        ("ml", "one milliliter", "milliliters"),
+        ("l", "one liter", "liters"),
+        ("°C", "one degree Celsius", "degrees Celsius"),
+        ("°F", "one degree Fahrenheit", "degrees Fahrenheit"),
+        ("°", "one degree", "degrees"),
+        ("%", "one percent", "percent"),
+        ("ms", "one millisecond", "milliseconds"),
+        ("min", "one minute", "minutes"),
+        ("h", "one hour", "hours"),
+        ("kB", "one kilobyte", "kilobytes"),
+        ("MB", "one megabyte", "megabytes"),
+        ("GB", "one gigabyte", "gigabytes"),
+        ("TB", "one terabyte", "terabytes"),
+        ("kWh", "one kilowatt hour", "kilowatt hours"),
+        ("kW", "one kilowatt", "kilowatts"),
+    ],
+    currencies: &[
+        Currency {
+            symbols: &["$", "USD"],
+            one: "one dollar",
+            many: "dollars",
+            minor_one: "one cent",
+            minor_many: "cents",
+        },
+        Currency {
+            symbols: &["€", "EUR"],
+            one: "one euro",
+            many: "euros",
+            minor_one: "one cent",
+            minor_many: "cents",
+        },
+        Currency {
+            symbols: &["£", "GBP"],
+            one: "one pound",
+            many: "pounds",
+            minor_one: "one penny",
+            minor_many: "pence",
+        },
+        Currency {
+            symbols: &["¥", "JPY"],
+            one: "one yen",
+            many: "yen",
+            minor_one: "one sen",
+            minor_many: "sen",
+        },
+        Currency {
+            symbols: &["CHF"],
+            one: "one franc",
+            many: "francs",
+            minor_one: "one centime",
+            minor_many: "centimes",
+        },
+    ],
+    currency_joiner: " and ",
+    symbols: &[
+        ('.', "dot"),
+        ('@', "at"),
+        ('/', "slash"),
+        (':', "colon"),
+        ('-', "dash"),
+        ('_', "underscore"),
+    ],
+};
+
+const GERMAN_ONES: [&str; 20] = [
+    "null",
+    "eins",
+    "zwei",
+    "drei",
+    "vier",
+    "fünf",
+    "sechs",
+    "sieben",
+    "acht",
+    "neun",
+    "zehn",
+    "elf",
+    "zwölf",
+    "dreizehn",
+    "vierzehn",
+    "fünfzehn",
+    "sechzehn",
+    "siebzehn",
+    "achtzehn",
+    "neunzehn",
+];
+const GERMAN_TENS: [&str; 10] = [
+    "", "", "zwanzig", "dreißig", "vierzig", "fünfzig", "sechzig", "siebzig", "achtzig", "neunzig",
+];
+/// Scales that are separate nouns, singular and plural
+const GERMAN_SCALES: [(u64, &str, &str); 5] = [
+    (1_000_000_000_000_000_000, "Trillion", "Trillionen"),
+    (1_000_000_000_000_000, "Billiarde", "Billiarden"),
+    (1_000_000_000_000, "Billion", "Billionen"),
+    (1_000_000_000, "Milliarde", "Milliarden"),
+    (1_000_000, "Million", "Millionen"),
+];
+const GERMAN_MONTHS: [&str; 12] = [
+    "Januar",
+    "Februar",
+    "März",
+    "April",
+    "Mai",
+    "Juni",
+    "Juli",
+    "August",
+    "September",
+    "Oktober",
+    "November",
+    "Dezember",
+];
+
+fn german_cardinal(n: u64) -> String {
+    if let Some((scale, one, many)) = GERMAN_SCALES.iter().find(|(scale, ..)| n >= *scale) {
+        let mut spoken = match n / scale {
+            1 => format!("eine {}", one),
+            count => format!("{} {}", german_cardinal(count), many),
+        };
+        let rest = n % scale;
+        if rest != 0 {
+            spoken.push(' ');
+            spoken.push_str(&german_cardinal(rest));
+        }
+        return spoken;
+    }
+    match n {
+        0 => GERMAN_ONES[0].to_string(),
+        1..=999 => german_below_thousand(n, true),
+        _ => {
+            let mut spoken = format!("{}tausend", german_below_thousand(n / 1000, false));
+            spoken.push_str(&german_below_thousand(n % 1000, true));
+            spoken
+        }
+    }
+}
+
+/// A trailing one is `eins` at the end of a number and `ein` before `tausend`
+fn german_below_thousand(n: u64, is_end: bool) -> String {
+    let prefix = |digit: u64| match digit {
+        1 => "ein",
+        _ => GERMAN_ONES[digit as usize],
+    };
+    let mut spoken = String::new();
+    if n >= 100 {
+        spoken.push_str(prefix(n / 100));
+        spoken.push_str("hundert");
+    }
+    match n % 100 {
+        0 => {}
+        1 if !is_end => spoken.push_str("ein"),
+        rest @ 1..=19 => spoken.push_str(GERMAN_ONES[rest as usize]),
+        rest => {
+            if rest % 10 > 0 {
+                spoken.push_str(prefix(rest % 10));
+                spoken.push_str("und");
+            }
+            spoken.push_str(GERMAN_TENS[(rest / 10) as usize]);
+        }
+    }
+    spoken
+}
+
+/// Declines the ordinal after the article or preposition before it,
+/// e.g. `der fünfte`, `am fünften` and `fünfter`
+fn german_ordinal(n: u64, previous: Option<&str>) -> Option<String> {
+    if n == 0 || n >= 1_000_000 {
+        return None;
+    }
+    let cardinal = german_cardinal(n);
+    let stem = match n % 100 {
+        1 => format!("{}erste", cardinal.strip_suffix("eins")?),
+        3 => format!("{}dritte", cardinal.strip_suffix("drei")?),
+        7 => format!("{}siebte", cardinal.strip_suffix("sieben")?),
+        8 => format!("{}e", cardinal),
+        2..=19 => format!("{}te", cardinal),
+        _ => format!("{}ste", cardinal),
+    };
+    let ending = match previous.map(str::to_lowercase).as_deref() {
+        Some("der" | "die" | "das") => "",
+        Some("am" | "vom" | "zum" | "im" | "beim" | "den" | "dem" | "des") => "n",
+        _ => "r",
+    };
+    Some(stem + ending)
+}
+
+/// Reads `1990` as `neunzehnhundertneunzig`
+fn german_year(n: u64) -> String {
+    match n {
+        1100..=1999 => format!(
+            "{}hundert{}",
+            german_cardinal(n / 100),
+            german_below_thousand(n % 100, true)
+        ),
+        _ => german_cardinal(n),
+    }
+}
+
+fn german_time(hours: u64, minutes: u64, _suffix: &str) -> String {
+    let hours = match hours {
+        1 => "ein".to_string(),
+        _ => german_cardinal(hours),
+    };
+    match minutes {
+        0 => format!("{} Uhr", hours),
+        _ => format!("{} Uhr {}", hours, german_cardinal(minutes)),
+    }
+}
+
+fn german_date(day: u64, month: usize, year: Option<u64>, previous: Option<&str>) -> String {
+    let mut spoken = format!(
+        "{} {}",
+        german_ordinal(day, previous).unwrap_or_default(),
+        GERMAN_MONTHS[month - 1]
+    );
+    if let Some(year) = year {
+        spoken.push(' ');
+        spoken.push_str(&german_year(year));
+    }
+    spoken
+}
+
+static GERMAN: Rules = Rules {
+    thousands_separator: '.',
+    decimal_separator: ',',
+    decimal_point: "Komma",
+    minus: "minus",
+    cardinal: german_cardinal,
+    ordinal: german_ordinal,
+    year: german_year,
+    time: german_time,
+    date: german_date,
+    date_formats: &[
+        ('-', DateOrder::Ymd),
+        ('.', DateOrder::Dmy),
+        ('/', DateOrder::Dmy),
+    ],
+    ordinal_suffixes: &[],
+    dotted_ordinals: true,
+    months: GERMAN_MONTHS,
+    time_words: &[("uhr", "")],
+    abbreviations: &[
+        ("z.B.", "zum Beispiel"),
+        ("d.h.", "das heißt"),
+        ("usw.", "und so weiter"),
+        ("bzw.", "beziehungsweise"),
+        ("ca.", "circa"),
+        ("Nr.", "Nummer"),
+        ("Dr.", "Doktor"),
+        ("Prof.", "Professor"),
+        ("Str.", "Straße"),
+        ("evtl.", "eventuell"),
+        ("inkl.", "inklusive"),
+        ("ggf.", "gegebenenfalls"),
+        ("vgl.", "vergleiche"),
+    ],
+    units: &[
+        ("km/h", "ein Kilometer pro Stunde", "Kilometer pro Stunde"),
+        ("km", "ein Kilometer", "Kilometer"),
+        ("cm", "ein Zentimeter", "Zentimeter"),
+        ("mm", "ein Millimeter", "Millimeter"),
+        ("m", "ein Meter", "Meter"),
+        ("kg", "ein Kilogramm", "Kilogramm"),
+        ("mg", "ein Milligramm", "Milligramm"),
+        ("g", "ein Gramm", "Gramm"),
+        ("ml", "ein Milliliter", "Milliliter"),
+        ("l", "ein Liter", "Liter"),
+        ("°C", "ein Grad Celsius", "Grad Celsius"),
+        ("°F", "ein Grad Fahrenheit", "Grad Fahrenheit"),
+        ("°", "ein Grad", "Grad"),
+        ("%", "ein Prozent", "Prozent"),
+        ("ms", "eine Millisekunde", "Millisekunden"),
+        ("min", "eine Minute", "Minuten"),
+        ("h", "eine Stunde", "Stunden"),
+        ("kB", "ein Kilobyte", "Kilobyte"),
+        ("MB", "ein Megabyte", "Megabyte"),
+        ("GB", "ein Gigabyte", "Gigabyte"),
+        ("TB", "ein Terabyte", "Terabyte"),
+        ("kWh", "eine Kilowattstunde", "Kilowattstunden"),
+        ("kW", "ein Kilowatt", "Kilowatt"),
+    ],
+    currencies: &[
+        Currency {
+            symbols: &["€", "EUR"],
+            one: "ein Euro",
+            many: "Euro",
+            minor_one: "ein Cent",
+            minor_many: "Cent",
+        },
+        Currency {
+            symbols: &["$", "USD"],
+            one: "ein Dollar",
+            many: "Dollar",
+            minor_one: "ein Cent",
+            minor_many: "Cent",
+        },
+        Currency {
+            symbols: &["£", "GBP"],
+            one: "ein Pfund",
+            many: "Pfund",
+            minor_one: "ein Penny",
+            minor_many: "Pence",
+        },
+        Currency {
+            symbols: &["¥", "JPY"],
+            one: "ein Yen",
+            many: "Yen",
+            minor_one: "ein Sen",
+            minor_many: "Sen",
+        },
+        Currency {
+            symbols: &["CHF"],
+            one: "ein Franken",
+            many: "Franken",
+            minor_one: "ein Rappen",
+            minor_many: "Rappen",
+        },
+    ],
+    currency_joiner: " und ",
+    symbols: &[
+        ('.', "Punkt"),
+        ('@', "ät"),
+        ('/', "Schrägstrich"),
+        (':', "Doppelpunkt"),
+        ('-', "Bindestrich"),
+        ('_', "Unterstrich"),
+    ],
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_english() {
+        let english = EnglishNormalizer;
+        assert_eq!(
+            english.normalize(
+                "Dr. Smith paid $1,234.50 on 2024-03-05 at 10:30 pm for 5 km, i.e. 21st place."
+            ),
+            "Doctor Smith paid one thousand two hundred thirty-four dollars and fifty cents \
+             on March fifth, twenty twenty-four at ten thirty p m for five kilometers, \
+             that is twenty-first place."
+        );
+        assert_eq!(
+            english.normalize("In 1990, 007 scored 50%.\nMail john.doe@example.com etc."),
+            "In nineteen ninety, zero zero seven scored fifty percent.\n\
+             Mail john dot doe at example dot com et cetera."
+        );
+        assert_eq!(
+            english.normalize("Visit https://example.org/docs/ at 9:05 for -1 °C and $0.99"),
+            "Visit example dot org slash docs at nine oh five for minus one degree Celsius \
+             and ninety-nine cents"
+        );
+    }
+
+    #[test]
+    fn test_german() {
+        let german = GermanNormalizer;
+        assert_eq!(
+            german.normalize(
+                "Am 5. März 2024 um 10:30 Uhr kostete es 3,50 € bzw. 1.990 Euro, z.B. bei 21 °C."
+            ),
+            "Am fünften März zweitausendvierundzwanzig um zehn Uhr dreißig kostete es \
+             drei Euro und fünfzig Cent beziehungsweise eintausendneunhundertneunzig Euro, \
+             zum Beispiel bei einundzwanzig Grad Celsius."
+        );
+        assert_eq!(
+            german.normalize("Der 01.10.1990 fuhr er 101 km/h bei 2,5 l."),
+            "Der erste Oktober neunzehnhundertneunzig fuhr er einhunderteins \
+             Kilometer pro Stunde bei zwei Komma fünf Liter."
+        );
+    }
+
+    #[test]
+    fn test_normalizer_options() {
+        struct Shout;
+        impl TextNormalizer for Shout {
+            fn normalize(&self, text: &str) -> String {
+                text.to_uppercase()
+            }
+        }
+        assert!(normalizer_for("en_US").is_some());
+        assert!(normalizer_for("de-de").is_some());
+        assert!(normalizer_for("xx").is_none());
+
+        let english = NormalizerOption::Language.resolve("en-gb").unwrap();
+        assert_eq!(english.normalize("5 km"), "five kilometers");
+        assert!(NormalizerOption::Language.resolve("xx").is_none());
+        assert!(NormalizerOption::Disabled.resolve("en-us").is_none());
+        let shout = NormalizerOption::Custom(Arc::new(Shout))
+            .resolve("xx")
+            .unwrap();
+        assert_eq!(shout.normalize("hi"), "HI");
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{ConfigIssue, PiperError, PiperResult, BOS, EOS, PAD};
+
+/// Controls how phonemes are converted to model input ids.
+///
+/// The defaults match piper-phonemize.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhonemeIdConfig {
+    pub pad: char,
+    pub bos: char,
+    pub eos: char,
+    /// Insert the pad id after the bos symbol and after every phoneme
+    pub intersperse_pad: bool,
+    pub add_bos: bool,
+    pub add_eos: bool,
+}
+
+impl Default for PhonemeIdConfig {
+    fn default() -> Self {
+        Self {
+            pad: PAD,
+            bos: BOS,
+            eos: EOS,
+            intersperse_pad: true,
+            add_bos: true,
+            add_eos: true,
+        }
+    }
+}
+
+/// Phoneme ids of one sentence
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PhonemeIds {
+    pub ids: Vec<i64>,
+    /// Phonemes that are not in the phoneme id map and were dropped,
+    /// with the number of times each one occurred
+    pub missing: BTreeMap<char, usize>,
+}
+
+/// Converts the phonemes of one sentence to ids the same way piper-phonemize does.
+///
+/// Every phoneme is first replaced by its `phoneme_map` substitutions (if any),
+/// then each resulting phoneme is expanded to all of its ids in `phoneme_id_map`.
+pub fn phonemes_to_ids(
+    phonemes: &str,
+    phoneme_id_map: &HashMap<char, Vec<i64>>,
+    phoneme_map: &HashMap<char, Vec<char>>,
+    config: &PhonemeIdConfig,
+) -> PiperResult<PhonemeIds> {
+    let symbol_ids = |symbol: char| match phoneme_id_map.get(&symbol) {
+        Some(ids) if !ids.is_empty() => Ok(ids.as_slice()),
+        _ => Err(PiperError::InvalidConfig(vec![
+            ConfigIssue::MissingSpecialSymbol(symbol),
+        ])),
+    };
+    let pad_ids: &[i64] = if config.intersperse_pad {
+        symbol_ids(config.pad)?
+    } else {
+        &[]
+    };
+
+    let mut result = PhonemeIds {
+        ids: Vec::with_capacity((phonemes.len() + 1) * 2),
+        missing: BTreeMap::new(),
+    };
+    if config.add_bos {
+        result.ids.extend_from_slice(symbol_ids(config.bos)?);
+        result.ids.extend_from_slice(pad_ids);
+    }
+    for phoneme in phonemes.chars() {
+        let substitutions = match phoneme_map.get(&phoneme) {
+            Some(mapped) => mapped.as_slice(),
+            None => std::slice::from_ref(&phoneme),
+        };
+        for phoneme in substitutions {
+            match phoneme_id_map.get(phoneme) {
+                Some(ids) => {
+                    result.ids.extend_from_slice(ids);
+                    result.ids.extend_from_slice(pad_ids);
+                }
+                None => *result.missing.entry(*phoneme).or_insert(0) += 1,
+            }
+        }
+    }
+    if config.add_eos {
+        result.ids.extend_from_slice(symbol_ids(config.eos)?);
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    /// Phonemes and ids of whole sentences, see `tests/fixtures/phoneme_ids`
+    #[derive(Deserialize)]
+    struct Fixture {
+        phoneme_id_map: HashMap<char, Vec<i64>>,
+        phoneme_map: HashMap<char, Vec<char>>,
+        sentences: Vec<FixtureSentence>,
+    }
+
+    #[derive(Deserialize)]
+    struct FixtureSentence {
+        phonemes: String,
+        ids: Vec<i64>,
+        missing: BTreeMap<char, usize>,
+    }
+
+    // Subset of the phoneme id map shipped with the en_US voices
+    const PHONEME_ID_MAP: &str = r#"{
+        "_": [0], "^": [1], "$": [2], " ": [3], "!": [4], ",": [8], ".": [10], "?": [13],
+        "a": [14], "b": [15], "d": [17], "e": [18], "h": [20], "i": [21], "k": [23],
+        "l": [24], "m": [25], "n": [26], "o": [27], "s": [31], "t": [32], "w": [35],
+        "z": [38], "æ": [39], "ð": [41], "ŋ": [44], "ɐ": [50], "ɑ": [51], "ɔ": [54],
+        "ə": [59], "ɚ": [60], "ɛ": [61], "ɜ": [62], "ɡ": [66], "ɪ": [74], "ɹ": [88], "ʊ": [100],
+        "ʌ": [102], "ˈ": [120], "ˌ": [121], "ː": [122]
+    }"#;
+
+    fn phoneme_id_map() -> HashMap<char, Vec<i64>> {
+        serde_json::from_str(PHONEME_ID_MAP).unwrap()
+    }
+
+    fn to_ids(phonemes: &str) -> PhonemeIds {
+        phonemes_to_ids(
+            phonemes,
+            &phoneme_id_map(),
+            &HashMap::new(),
+            &PhonemeIdConfig::default(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_matches_piper_phonemize() {
+        // Ids of "This is a test."
+        assert_eq!(
+            to_ids("ðɪs ɪz ɐ tˈɛst.").ids,
+            vec![
+                1, 0, 41, 0, 74, 0, 31, 0, 3, 0, 74, 0, 38, 0, 3, 0, 50, 0, 3, 0, 32, 0, 120, 0,
+                61, 0, 31, 0, 32, 0, 10, 0, 2
+            ]
+        );
+        // Ids of "Hello world!"
+        assert_eq!(
+            to_ids("həlˈoʊ wˈɜːld!").ids,
+            vec![
+                1, 0, 20, 0, 59, 0, 24, 0, 120, 0, 27, 0, 100, 0, 3, 0, 35, 0, 120, 0, 62, 0, 122,
+                0, 24, 0, 17, 0, 4, 0, 2
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fixtures() {
+        let fixtures = [
+            (
+                "en_us",
+                include_str!("../tests/fixtures/phoneme_ids/en_us.json"),
+            ),
+            (
+                "multi_id",
+                include_str!("../tests/fixtures/phoneme_ids/multi_id.json"),
+            ),
+        ];
+        for (name, json) in fixtures {
+            let fixture: Fixture = serde_json::from_str(json).unwrap();
+            for sentence in fixture.sentences {
+                let result = phonemes_to_ids(
+                    &sentence.phonemes,
+                    &fixture.phoneme_id_map,
+                    &fixture.phoneme_map,
+                    &PhonemeIdConfig::default(),
+                )
+                .unwrap();
+                assert_eq!(result.ids, sentence.ids, "{}: {}", name, sentence.phonemes);
+                assert_eq!(
+                    result.missing, sentence.missing,
+                    "{}: {}",
+                    name, sentence.phonemes
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_reports_missing_phonemes() {
+        let result = to_ids("aʒbaʒ");
+        assert_eq!(result.ids, vec![1, 0, 14, 0, 15, 0, 14, 0, 2]);
+        assert_eq!(result.missing, BTreeMap::from([('ʒ', 2)]));
+    }
+
+    #[test]
+    fn test_multi_id_phonemes_and_substitutions() {
+        let mut phoneme_id_map = phoneme_id_map();
+        phoneme_id_map.insert('ʧ', vec![32, 121]);
+        let phoneme_map = HashMap::from([('ɡ', vec!['k']), ('x', vec!['k', 's'])]);
+        let result = phonemes_to_ids(
+            "ʧɡx",
+            &phoneme_id_map,
+            &phoneme_map,
+            &PhonemeIdConfig::default(),
+        )
+        .unwrap();
+        assert_eq!(result.ids, vec![1, 0, 32, 121, 0, 23, 0, 23, 0, 31, 0, 2]);
+        assert!(result.missing.is_empty());
+    }
+
+    #[test]
+    fn test_without_padding() {
+        let config = PhonemeIdConfig {
+            intersperse_pad: false,
+            add_eos: false,
+            ..Default::default()
+        };
+        let result = phonemes_to_ids("ab", &phoneme_id_map(), &HashMap::new(), &config).unwrap();
+        assert_eq!(result.ids, vec![1, 14, 15]);
+    }
+}
@@ -0,0 +1,364 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::synth::PiperSpeechSynthesizer;
+use crate::{
+    load_model_config, ModelOptions, PiperError, PiperModel, PiperModelBuilder, PiperResult,
+    VoiceOptions,
+};
+
+const CONFIG_EXTENSION: &str = ".onnx.json";
+
+/// A voice found by [`VoiceRegistry::scan`]
+#[derive(Debug, Clone)]
+pub struct VoiceInfo {
+    /// `key` from the config, or the config filename without `.onnx.json`
+    pub key: String,
+    pub language: Option<String>,
+    pub quality: Option<String>,
+    pub config_path: PathBuf,
+    pub streaming: bool,
+    /// Size of the ONNX files on disk, used to estimate the memory of a loaded voice
+    pub size_bytes: u64,
+}
+
+struct LoadedVoice {
+    model: Arc<dyn PiperModel + Send + Sync>,
+    size_bytes: u64,
+    last_used: Instant,
+}
+
+/// Indexes a directory of voices and loads them on first use.
+///
+/// Loaded voices are cached and evicted least recently used first once the number of loaded
+/// voices or their total size exceeds the configured limits, or after they have been idle
+/// for too long. Handles that were already given out keep their model alive after eviction.
+pub struct VoiceRegistry {
+    voices: HashMap<String, VoiceInfo>,
+    scan_errors: Vec<(PathBuf, PiperError)>,
+    options: ModelOptions,
+    voice_options: VoiceOptions,
+    max_loaded_voices: Option<usize>,
+    memory_budget: Option<u64>,
+    idle_timeout: Option<Duration>,
+    loaded: Mutex<HashMap<String, LoadedVoice>>,
+}
+
+impl VoiceRegistry {
+    /// Recursively scans `dir` for `*.onnx.json` voice configs.
+    ///
+    /// Configs that fail to load are skipped and reported by [`Self::scan_errors`].
+    pub fn scan(dir: impl AsRef<Path>) -> PiperResult<Self> {
+        let mut config_paths = Vec::new();
+        find_voice_configs(dir.as_ref(), &mut config_paths)?;
+        config_paths.sort();
+
+        let mut voices = HashMap::new();
+        let mut scan_errors = Vec::new();
+        for config_path in config_paths {
+            match read_voice_info(&config_path) {
+                Ok(info) => {
+                    if voices.contains_key(&info.key) {
+                        scan_errors.push((
+                            config_path,
+                            PiperError::FailedToLoadResource(format!(
+                                "Duplicate voice key `{}`",
+                                info.key
+                            )),
+                        ));
+                    } else {
+                        voices.insert(info.key.clone(), info);
+                    }
+                }
+                Err(e) => scan_errors.push((config_path, e)),
+            }
+        }
+        Ok(Self {
+            voices,
+            scan_errors,
+            options: ModelOptions::default(),
+            voice_options: VoiceOptions::default(),
+            max_loaded_voices: None,
+            memory_budget: None,
+            idle_timeout: None,
+            loaded: Mutex::new(HashMap::new()),
+        })
+    }
+    /// Options used to load every voice of the registry
+    pub fn options(mut self, options: ModelOptions) -> Self {
+        self.options = options;
+        self
+    }
+    /// Voice options used to load every voice of the registry
+    pub fn voice_options(mut self, voice_options: VoiceOptions) -> Self {
+        self.voice_options = voice_options;
+        self
+    }
+    pub fn max_loaded_voices(mut self, max_loaded_voices: usize) -> Self {
+        self.max_loaded_voices = Some(max_loaded_voices);
+        self
+    }
+    /// Upper bound for the total size of the loaded voices, in bytes
+    pub fn memory_budget(mut self, memory_budget: u64) -> Self {
+        self.memory_budget = Some(memory_budget);
+        self
+    }
+    /// Evict voices that have not been used for this long
+    pub fn idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = Some(idle_timeout);
+        self
+    }
+
+    pub fn voices(&self) -> impl Iterator<Item = &VoiceInfo> {
+        self.voices.values()
+    }
+    pub fn voice(&self, key: &str) -> Option<&VoiceInfo> {
+        self.voices.get(key)
+    }
+    /// Voices matching the given language code (e.g. `en_US`) and quality, if given
+    pub fn find(&self, language: Option<&str>, quality: Option<&str>) -> Vec<&VoiceInfo> {
+        let mut voices = Vec::from_iter(self.voices.values().filter(|voice| {
+            language.is_none_or(|language| voice.language.as_deref() == Some(language))
+                && quality.is_none_or(|quality| voice.quality.as_deref() == Some(quality))
+        }));
+        voices.sort_by(|a, b| a.key.cmp(&b.key));
+        voices
+    }
+    pub fn scan_errors(&self) -> &[(PathBuf, PiperError)] {
+        &self.scan_errors
+    }
+
+    /// Returns a shared handle to the voice, loading it if needed
+    pub fn get(&self, key: &str) -> PiperResult<Arc<dyn PiperModel + Send + Sync>> {
+        let Some(info) = self.voices.get(key) else {
+            return Err(PiperError::VoiceNotFound(key.to_string()));
+        };
+        if let Some(voice) = self.lock_loaded().get_mut(key) {
+            voice.last_used = Instant::now();
+            return Ok(Arc::clone(&voice.model));
+        }
+
+        // Load without holding the lock, so other voices stay available meanwhile
+        let model = PiperModelBuilder::new(&info.config_path)
+            .options(self.options.clone())
+            .voice_options(self.voice_options.clone())
+            .build()?;
+
+        let mut loaded = self.lock_loaded();
+        let model = match loaded.get_mut(key) {
+            // Loaded concurrently by another caller, keep a single instance
+            Some(voice) => {
+                voice.last_used = Instant::now();
+                Arc::clone(&voice.model)
+            }
+            None => {
+                loaded.insert(
+                    key.to_string(),
+                    LoadedVoice {
+                        model: Arc::clone(&model),
+                        size_bytes: info.size_bytes,
+                        last_used: Instant::now(),
+                    },
+                );
+                model
+            }
+        };
+        self.evict(&mut loaded, Some(key));
+        Ok(model)
+    }
+    pub fn synthesizer(&self, key: &str) -> PiperResult<PiperSpeechSynthesizer> {
+        PiperSpeechSynthesizer::new(self.get(key)?)
+    }
+    pub fn is_loaded(&self, key: &str) -> bool {
+        self.lock_loaded().contains_key(key)
+    }
+    pub fn loaded_voices(&self) -> Vec<String> {
+        let mut keys = Vec::from_iter(self.lock_loaded().keys().cloned());
+        keys.sort();
+        keys
+    }
+    /// Drops the registry's handle to the voice. Returns `false` if it was not loaded.
+    pub fn unload(&self, key: &str) -> bool {
+        self.lock_loaded().remove(key).is_some()
+    }
+    /// Evicts the voices that exceeded the idle timeout. Eviction otherwise only runs
+    /// when a voice is loaded, so long-running services may call this periodically.
+    pub fn evict_idle(&self) {
+        self.evict(&mut self.lock_loaded(), None);
+    }
+
+    fn evict(&self, loaded: &mut HashMap<String, LoadedVoice>, keep: Option<&str>) {
+        if let Some(idle_timeout) = self.idle_timeout {
+            loaded.retain(|key, voice| {
+                Some(key.as_str()) == keep || voice.last_used.elapsed() < idle_timeout
+            });
+        }
+        loop {
+            let total_size: u64 = loaded.values().map(|voice| voice.size_bytes).sum();
+            let over_count = self
+                .max_loaded_voices
+                .is_some_and(|max_loaded_voices| loaded.len() > max_loaded_voices);
+            let over_budget = self
+                .memory_budget
+                .is_some_and(|memory_budget| total_size > memory_budget);
+            if !over_count && !over_budget {
+                break;
+            }
+            let least_recently_used = loaded
+                .iter()
+                .filter(|(key, _)| Some(key.as_str()) != keep)
+                .min_by_key(|(_, voice)| voice.last_used)
+                .map(|(key, _)| key.clone());
+            match least_recently_used {
+                Some(key) => {
+                    loaded.remove(&key);
+                }
+                // Only the voice in use is left, keep it even if it exceeds the limits
+                None => break,
+            }
+        }
+    }
+    fn lock_loaded(&self) -> MutexGuard<'_, HashMap<String, LoadedVoice>> {
+        // The cache stays consistent even if a holder of the lock panicked
+        match self.loaded.lock() {
+            Ok(loaded) => loaded,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+fn find_voice_configs(dir: &Path, config_paths: &mut Vec<PathBuf>) -> PiperResult<()> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            return Err(PiperError::MissingFile {
+                path: dir.to_path_buf(),
+                source: e,
+            })
+        }
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            find_voice_configs(&path, config_paths)?;
+        } else if path.to_string_lossy().ends_with(CONFIG_EXTENSION) {
+            config_paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn read_voice_info(config_path: &Path) -> PiperResult<VoiceInfo> {
+    let (config, _) = load_model_config(config_path)?;
+    let key = match config.key {
+        Some(key) => key,
+        None => {
+            let filename = config_path.to_string_lossy();
+            let filename = filename.trim_end_matches(CONFIG_EXTENSION);
+            Path::new(filename)
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        }
+    };
+    let streaming = config.streaming.unwrap_or_default();
+    let model_files = if streaming {
+        vec![
+            config_path.with_file_name("encoder.onnx"),
+            config_path.with_file_name("decoder.onnx"),
+        ]
+    } else {
+        vec![config_path.with_extension("")]
+    };
+    let size_bytes = model_files
+        .iter()
+        .filter_map(|path| fs::metadata(path).ok())
+        .map(|metadata| metadata.len())
+        .sum();
+    Ok(VoiceInfo {
+        key,
+        language: config.language.map(|language| language.code),
+        quality: config.audio.quality,
+        config_path: config_path.to_path_buf(),
+        streaming,
+        size_bytes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn voice_config(key: &str, language: &str, quality: &str) -> String {
+        format!(
+            r#"{{
+                "key": "{key}",
+                "language": {{ "code": "{language}" }},
+                "audio": {{ "sample_rate": 22050, "quality": "{quality}" }},
+                "num_speakers": 1,
+                "speaker_id_map": {{}},
+                "espeak": {{ "voice": "en-us" }},
+                "inference": {{ "noise_scale": 0.667, "length_scale": 1.0, "noise_w": 0.8 }},
+                "num_symbols": 4,
+                "phoneme_id_map": {{ "_": [0], "^": [1], "$": [2], "a": [3] }}
+            }}"#
+        )
+    }
+
+    #[test]
+    fn test_scan_indexes_voices() {
+        let dir = std::env::temp_dir().join(format!("piper-rs-registry-{}", std::process::id()));
+        let nested = dir.join("de").join("thorsten");
+        fs::create_dir_all(&nested).unwrap();
+        let voices = [
+            (
+                dir.join("en_US-lessac-medium.onnx.json"),
+                "en_US-lessac-medium",
+                "en_US",
+                "medium",
+            ),
+            (
+                dir.join("en_US-amy-low.onnx.json"),
+                "en_US-amy-low",
+                "en_US",
+                "low",
+            ),
+            (
+                nested.join("de_DE-thorsten-high.onnx.json"),
+                "de_DE-thorsten-high",
+                "de_DE",
+                "high",
+            ),
+        ];
+        for (path, key, language, quality) in &voices {
+            fs::write(path, voice_config(key, language, quality)).unwrap();
+        }
+        fs::write(dir.join("broken.onnx.json"), "{}").unwrap();
+
+        let registry = VoiceRegistry::scan(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(registry.voices().count(), 3);
+        assert_eq!(registry.scan_errors().len(), 1);
+        assert_eq!(
+            registry.voice("de_DE-thorsten-high").unwrap().config_path,
+            voices[2].0
+        );
+        let keys = Vec::from_iter(
+            registry
+                .find(Some("en_US"), None)
+                .into_iter()
+                .map(|voice| voice.key.as_str()),
+        );
+        assert_eq!(keys, ["en_US-amy-low", "en_US-lessac-medium"]);
+        assert_eq!(registry.find(Some("en_US"), Some("low")).len(), 1);
+        assert!(matches!(
+            registry.get("fr_FR-missing-low"),
+            Err(PiperError::VoiceNotFound(_))
+        ));
+        assert!(registry.loaded_voices().is_empty());
+    }
+}
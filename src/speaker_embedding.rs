@@ -0,0 +1,139 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+use crate::{PiperError, PiperResult};
+
+/// The conditioning vector (`g`) a multi-speaker model derives from a speaker id
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpeakerEmbedding {
+    pub values: Vec<f32>,
+}
+
+impl SpeakerEmbedding {
+    pub fn new(values: Vec<f32>) -> Self {
+        Self { values }
+    }
+    /// Weighted average of several embeddings. Weights are normalized to sum to one.
+    pub fn blend(weighted: &[(SpeakerEmbedding, f32)]) -> PiperResult<Self> {
+        let Some((first, _)) = weighted.first() else {
+            return Err(PiperError::InvalidSpeaker(
+                "At least one speaker embedding is needed for blending".to_string(),
+            ));
+        };
+        let total_weight: f32 = weighted.iter().map(|(_, weight)| weight).sum();
+        let valid_weight = |weight: f32| weight.is_finite() && weight >= 0.0;
+        if !weighted.iter().all(|(_, weight)| valid_weight(*weight))
+            || !total_weight.is_finite()
+            || total_weight <= 0.0
+        {
+            return Err(PiperError::InvalidSpeaker(
+                "Blending weights must be finite, non-negative and not all zero".to_string(),
+            ));
+        }
+        let mut values = vec![0.0f32; first.values.len()];
+        for (embedding, weight) in weighted {
+            if embedding.values.len() != values.len() {
+                return Err(PiperError::InvalidSpeaker(format!(
+                    "Cannot blend speaker embeddings of size {} and {}",
+                    values.len(),
+                    embedding.values.len()
+                )));
+            }
+            let weight = weight / total_weight;
+            for (value, other) in values.iter_mut().zip(&embedding.values) {
+                *value += other * weight;
+            }
+        }
+        Ok(Self { values })
+    }
+}
+
+/// Named speaker embeddings (e.g. blends) persisted as a JSON file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SpeakerEmbeddingStore(BTreeMap<String, SpeakerEmbedding>);
+
+impl SpeakerEmbeddingStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn load(path: &Path) -> PiperResult<Self> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(why) => {
+                return Err(PiperError::MissingFile {
+                    path: path.to_path_buf(),
+                    source: why,
+                })
+            }
+        };
+        match serde_json::from_reader(BufReader::new(file)) {
+            Ok(store) => Ok(store),
+            Err(why) => Err(PiperError::ConfigParse {
+                path: Some(path.to_path_buf()),
+                source: why,
+            }),
+        }
+    }
+    pub fn save(&self, path: &Path) -> PiperResult<()> {
+        let file = match File::create(path) {
+            Ok(file) => file,
+            Err(why) => {
+                return Err(PiperError::OperationError(format!(
+                    "Failed to create speaker embeddings file: `{}`. Caused by: `{}`",
+                    path.display(),
+                    why
+                )))
+            }
+        };
+        match serde_json::to_writer_pretty(BufWriter::new(file), self) {
+            Ok(()) => Ok(()),
+            Err(why) => Err(PiperError::OperationError(format!(
+                "Failed to write speaker embeddings to file: `{}`. Caused by: `{}`",
+                path.display(),
+                why
+            ))),
+        }
+    }
+    pub fn insert(&mut self, name: impl Into<String>, embedding: SpeakerEmbedding) {
+        self.0.insert(name.into(), embedding);
+    }
+    pub fn get(&self, name: &str) -> Option<&SpeakerEmbedding> {
+        self.0.get(name)
+    }
+    pub fn remove(&mut self, name: &str) -> Option<SpeakerEmbedding> {
+        self.0.remove(name)
+    }
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.0.keys()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blend() {
+        let a = SpeakerEmbedding::new(vec![1.0, 0.0, 2.0]);
+        let b = SpeakerEmbedding::new(vec![0.0, 1.0, 4.0]);
+        let blended = SpeakerEmbedding::blend(&[(a.clone(), 3.0), (b.clone(), 1.0)]).unwrap();
+        assert_eq!(blended.values, vec![0.75, 0.25, 2.5]);
+
+        let is_invalid = |weighted: &[(SpeakerEmbedding, f32)]| {
+            matches!(
+                SpeakerEmbedding::blend(weighted),
+                Err(PiperError::InvalidSpeaker(_))
+            )
+        };
+        let mismatched = SpeakerEmbedding::new(vec![1.0]);
+        assert!(is_invalid(&[(a.clone(), 1.0), (mismatched, 1.0)]));
+        assert!(is_invalid(&[(a.clone(), f32::NAN), (b.clone(), 1.0)]));
+        assert!(is_invalid(&[(a.clone(), f32::INFINITY), (b.clone(), 1.0)]));
+        assert!(is_invalid(&[(a, 0.0), (b, 0.0)]));
+        assert!(is_invalid(&[]));
+    }
+}
@@ -0,0 +1,670 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::{PiperError, PiperResult};
+
+/// Pause after a `<p>` paragraph
+const PARAGRAPH_BREAK: Duration = Duration::from_millis(750);
+const BREAK_STRENGTHS: [(&str, u64); 6] = [
+    ("none", 0),
+    ("x-weak", 100),
+    ("weak", 250),
+    ("medium", 500),
+    ("strong", 750),
+    ("x-strong", 1000),
+];
+const RATE_KEYWORDS: [(&str, f32); 6] = [
+    ("x-slow", 0.5),
+    ("slow", 0.75),
+    ("medium", 1.0),
+    ("fast", 1.25),
+    ("x-fast", 1.75),
+    ("default", 1.0),
+];
+const PITCH_KEYWORDS: [(&str, f32); 6] = [
+    ("x-low", 0.7),
+    ("low", 0.85),
+    ("medium", 1.0),
+    ("high", 1.15),
+    ("x-high", 1.3),
+    ("default", 1.0),
+];
+const VOLUME_KEYWORDS: [(&str, f32); 7] = [
+    ("silent", 0.0),
+    ("x-soft", 0.25),
+    ("soft", 0.5),
+    ("medium", 1.0),
+    ("loud", 1.5),
+    ("x-loud", 2.0),
+    ("default", 1.0),
+];
+/// English month names accepted in `say-as` dates
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+/// Rate, pitch and volume of a span of SSML, as factors of the voice's own
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Prosody {
+    pub rate: f32,
+    pub pitch: f32,
+    pub volume: f32,
+}
+
+impl Default for Prosody {
+    fn default() -> Self {
+        Self {
+            rate: 1.0,
+            pitch: 1.0,
+            volume: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SsmlPiece {
+    /// Text to phonemize
+    Text(String),
+    /// IPA phonemes of a `<phoneme>` element, passed to the model as is
+    Phonemes(String),
+}
+
+/// A run of speech with the same prosody and voice, or a pause.
+/// `<s>` and `<p>` elements always start a new run.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SsmlSegment {
+    Speech {
+        pieces: Vec<SsmlPiece>,
+        prosody: Prosody,
+        /// Speaker name of the enclosing `<voice>` element
+        voice: Option<String>,
+    },
+    Break(Duration),
+}
+
+/// Parses the supported subset of SSML: `<speak>`, `<p>`, `<s>`, `<break>`, `<prosody>`,
+/// `<say-as>`, `<phoneme>`, `<voice>` and `<sub>`.
+///
+/// Other elements are ignored, but the text inside them is still spoken.
+/// Full `say-as` dates are rewritten as ISO dates, which the voice's text normalizer
+/// reads in its language. Partial dates are spoken as written.
+pub fn parse_ssml(ssml: &str) -> PiperResult<Vec<SsmlSegment>> {
+    let nodes = Parser {
+        input: ssml,
+        position: 0,
+    }
+    .parse_children(None)?;
+    let mut builder = SegmentBuilder::default();
+    builder.walk(&nodes, &Context::default())?;
+    builder.flush();
+    Ok(builder.segments)
+}
+
+fn invalid(message: impl Into<String>) -> PiperError {
+    PiperError::InvalidSsml(message.into())
+}
+
+enum Node {
+    Text(String),
+    Element {
+        name: String,
+        attributes: HashMap<String, String>,
+        children: Vec<Node>,
+    },
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+impl Parser<'_> {
+    /// Parses nodes up to the end tag of `parent`, or to the end of the input
+    fn parse_children(&mut self, parent: Option<&str>) -> PiperResult<Vec<Node>> {
+        let mut nodes = Vec::new();
+        loop {
+            let rest = &self.input[self.position..];
+            if rest.is_empty() {
+                return match parent {
+                    Some(parent) => Err(invalid(format!("`<{}>` is not closed", parent))),
+                    None => Ok(nodes),
+                };
+            }
+            if let Some(comment) = rest.strip_prefix("<!--") {
+                let end = comment
+                    .find("-->")
+                    .ok_or_else(|| invalid("unterminated comment"))?;
+                self.position += "<!--".len() + end + "-->".len();
+            } else if let Some(cdata) = rest.strip_prefix("<![CDATA[") {
+                let end = cdata
+                    .find("]]>")
+                    .ok_or_else(|| invalid("unterminated CDATA section"))?;
+                nodes.push(Node::Text(cdata[..end].to_string()));
+                self.position += "<![CDATA[".len() + end + "]]>".len();
+            } else if rest.starts_with("<?") || rest.starts_with("<!") {
+                // XML declaration or doctype
+                let end = rest.find('>').ok_or_else(|| invalid("unterminated tag"))?;
+                self.position += end + 1;
+            } else if let Some(end_tag) = rest.strip_prefix("</") {
+                let end = end_tag
+                    .find('>')
+                    .ok_or_else(|| invalid("unterminated tag"))?;
+                let name = end_tag[..end].trim();
+                self.position += "</".len() + end + 1;
+                return match parent {
+                    Some(parent) if parent == name => Ok(nodes),
+                    _ => Err(invalid(format!("unexpected `</{}>`", name))),
+                };
+            } else if rest.starts_with('<') {
+                let (name, attributes, self_closing) = self.parse_start_tag()?;
+                let children = if self_closing {
+                    Vec::new()
+                } else {
+                    self.parse_children(Some(&name))?
+                };
+                nodes.push(Node::Element {
+                    name,
+                    attributes,
+                    children,
+                });
+            } else {
+                let end = rest.find('<').unwrap_or(rest.len());
+                nodes.push(Node::Text(decode_entities(&rest[..end])?));
+                self.position += end;
+            }
+        }
+    }
+    fn parse_start_tag(&mut self) -> PiperResult<(String, HashMap<String, String>, bool)> {
+        let rest = &self.input[self.position + 1..];
+        // `>` may appear inside quoted attribute values
+        let mut quote = None;
+        let mut end = None;
+        for (index, c) in rest.char_indices() {
+            match quote {
+                Some(open) if c == open => quote = None,
+                Some(_) => {}
+                None if c == '"' || c == '\'' => quote = Some(c),
+                None if c == '>' => {
+                    end = Some(index);
+                    break;
+                }
+                None => {}
+            }
+        }
+        let end = end.ok_or_else(|| invalid("unterminated tag"))?;
+        self.position += 1 + end + 1;
+        let tag = &rest[..end];
+        let (tag, self_closing) = match tag.strip_suffix('/') {
+            Some(tag) => (tag, true),
+            None => (tag, false),
+        };
+        let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+        let name = &tag[..name_end];
+        if name.is_empty() {
+            return Err(invalid("a tag has no name"));
+        }
+        let attributes = parse_attributes(&tag[name_end..])?;
+        Ok((name.to_string(), attributes, self_closing))
+    }
+}
+
+fn parse_attributes(mut input: &str) -> PiperResult<HashMap<String, String>> {
+    let mut attributes = HashMap::new();
+    loop {
+        input = input.trim_start();
+        if input.is_empty() {
+            return Ok(attributes);
+        }
+        let Some(equals) = input.find('=') else {
+            return Err(invalid(format!("attribute `{}` has no value", input)));
+        };
+        let name = input[..equals].trim();
+        let value = input[equals + 1..].trim_start();
+        let Some(quote) = value.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+            return Err(invalid(format!(
+                "the value of attribute `{}` is not quoted",
+                name
+            )));
+        };
+        let Some(value_end) = value[1..].find(quote) else {
+            return Err(invalid(format!(
+                "the value of attribute `{}` is not terminated",
+                name
+            )));
+        };
+        attributes.insert(name.to_string(), decode_entities(&value[1..1 + value_end])?);
+        input = &value[1 + value_end + 1..];
+    }
+}
+
+fn decode_entities(text: &str) -> PiperResult<String> {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+        let end = rest
+            .find(';')
+            .ok_or_else(|| invalid("unterminated entity"))?;
+        let entity = &rest[..end];
+        let c = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => {
+                let code = match entity.strip_prefix("#x") {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => entity.strip_prefix('#').and_then(|dec| dec.parse().ok()),
+                };
+                code.and_then(char::from_u32)
+            }
+        };
+        match c {
+            Some(c) => decoded.push(c),
+            None => return Err(invalid(format!("unknown entity `&{};`", entity))),
+        }
+        rest = &rest[end + 1..];
+    }
+    decoded.push_str(rest);
+    Ok(decoded)
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Context {
+    prosody: Prosody,
+    voice: Option<String>,
+}
+
+#[derive(Default)]
+struct SegmentBuilder {
+    segments: Vec<SsmlSegment>,
+    /// Pieces of the current run and the context they share
+    pieces: Vec<SsmlPiece>,
+    context: Context,
+}
+
+impl SegmentBuilder {
+    fn walk(&mut self, nodes: &[Node], context: &Context) -> PiperResult<()> {
+        for node in nodes {
+            match node {
+                Node::Text(text) => self.push(SsmlPiece::Text(text.clone()), context),
+                Node::Element {
+                    name,
+                    attributes,
+                    children,
+                } => self.element(name, attributes, children, context)?,
+            }
+        }
+        Ok(())
+    }
+    fn element(
+        &mut self,
+        name: &str,
+        attributes: &HashMap<String, String>,
+        children: &[Node],
+        context: &Context,
+    ) -> PiperResult<()> {
+        let attribute = |key: &str| attributes.get(key).map(String::as_str);
+        match name {
+            "p" | "s" => {
+                self.flush();
+                self.walk(children, context)?;
+                self.flush();
+                if name == "p" {
+                    self.push_break(PARAGRAPH_BREAK);
+                }
+            }
+            "break" => self.push_break(parse_break(attribute("time"), attribute("strength"))?),
+            "prosody" => {
+                let current = context.prosody;
+                let prosody = Prosody {
+                    rate: parse_prosody("rate", attribute("rate"), current.rate, &RATE_KEYWORDS)?,
+                    pitch: parse_prosody(
+                        "pitch",
+                        attribute("pitch"),
+                        current.pitch,
+                        &PITCH_KEYWORDS,
+                    )?,
+                    volume: parse_prosody(
+                        "volume",
+                        attribute("volume"),
+                        current.volume,
+                        &VOLUME_KEYWORDS,
+                    )?,
+                };
+                let context = Context {
+                    prosody,
+                    voice: context.voice.clone(),
+                };
+                self.walk(children, &context)?;
+            }
+            "voice" => {
+                let Some(voice) = attribute("name") else {
+                    return Err(invalid("`<voice>` needs a `name` attribute"));
+                };
+                let context = Context {
+                    prosody: context.prosody,
+                    voice: Some(voice.to_string()),
+                };
+                self.walk(children, &context)?;
+            }
+            "say-as" => {
+                let text = inner_text(children);
+                let text = match attribute("interpret-as") {
+                    Some("characters" | "spell-out" | "digits") => spell_out(&text),
+                    Some("date") => {
+                        iso_date(&text, attribute("format").unwrap_or("ymd")).unwrap_or(text)
+                    }
+                    _ => text,
+                };
+                self.push(SsmlPiece::Text(text), context);
+            }
+            "sub" => {
+                let Some(alias) = attribute("alias") else {
+                    return Err(invalid("`<sub>` needs an `alias` attribute"));
+                };
+                self.push(SsmlPiece::Text(alias.to_string()), context);
+            }
+            "phoneme" => {
+                match attribute("alphabet") {
+                    None | Some("ipa") => {}
+                    Some(alphabet) => {
+                        return Err(invalid(format!(
+                            "the `{}` phoneme alphabet is not supported, use `ipa`",
+                            alphabet
+                        )))
+                    }
+                }
+                let Some(phonemes) = attribute("ph") else {
+                    return Err(invalid("`<phoneme>` needs a `ph` attribute"));
+                };
+                self.push(SsmlPiece::Phonemes(phonemes.to_string()), context);
+            }
+            // Not spoken
+            "mark" | "desc" => {}
+            // `<speak>`, and elements whose text is spoken as is, e.g. `<emphasis>`
+            _ => self.walk(children, context)?,
+        }
+        Ok(())
+    }
+    fn push(&mut self, piece: SsmlPiece, context: &Context) {
+        if matches!(piece, SsmlPiece::Text(ref text) if text.trim().is_empty())
+            && self.pieces.is_empty()
+        {
+            return;
+        }
+        if self.context != *context {
+            self.flush();
+            self.context = context.clone();
+        }
+        match (self.pieces.last_mut(), piece) {
+            (Some(SsmlPiece::Text(last)), SsmlPiece::Text(text)) => last.push_str(&text),
+            (_, piece) => self.pieces.push(piece),
+        }
+    }
+    /// Ends the current run
+    fn flush(&mut self) {
+        let pieces = std::mem::take(&mut self.pieces);
+        let has_speech = pieces.iter().any(|piece| match piece {
+            SsmlPiece::Text(text) => !text.trim().is_empty(),
+            SsmlPiece::Phonemes(_) => true,
+        });
+        if has_speech {
+            self.segments.push(SsmlSegment::Speech {
+                pieces,
+                prosody: self.context.prosody,
+                voice: self.context.voice.clone(),
+            });
+        }
+    }
+    fn push_break(&mut self, duration: Duration) {
+        self.flush();
+        match self.segments.last_mut() {
+            Some(SsmlSegment::Break(previous)) => *previous += duration,
+            _ => self.segments.push(SsmlSegment::Break(duration)),
+        }
+    }
+}
+
+fn inner_text(nodes: &[Node]) -> String {
+    let mut text = String::new();
+    for node in nodes {
+        match node {
+            Node::Text(t) => text.push_str(t),
+            Node::Element { children, .. } => text.push_str(&inner_text(children)),
+        }
+    }
+    text
+}
+
+fn parse_break(time: Option<&str>, strength: Option<&str>) -> PiperResult<Duration> {
+    if let Some(time) = time.map(str::trim) {
+        let (value, millis_per_unit) = match time.strip_suffix("ms") {
+            Some(millis) => (millis, 1.0),
+            None => match time.strip_suffix('s') {
+                Some(seconds) => (seconds, 1000.0),
+                None => return Err(invalid(format!("unsupported break time `{}`", time))),
+            },
+        };
+        return match value.trim().parse::<f32>() {
+            Ok(value) if value.is_finite() && value >= 0.0 => Ok(Duration::from_millis(
+                (value * millis_per_unit).round() as u64,
+            )),
+            _ => Err(invalid(format!("unsupported break time `{}`", time))),
+        };
+    }
+    let strength = strength.unwrap_or("medium");
+    match BREAK_STRENGTHS.iter().find(|(name, _)| *name == strength) {
+        Some((_, millis)) => Ok(Duration::from_millis(*millis)),
+        None => Err(invalid(format!(
+            "unsupported break strength `{}`",
+            strength
+        ))),
+    }
+}
+
+/// Reads a prosody attribute as a factor of the voice's own value.
+/// Keywords and plain values are absolute, signed values are relative to `current`.
+fn parse_prosody(
+    attribute: &str,
+    value: Option<&str>,
+    current: f32,
+    keywords: &[(&str, f32)],
+) -> PiperResult<f32> {
+    let Some(value) = value.map(str::trim) else {
+        return Ok(current);
+    };
+    if let Some((_, factor)) = keywords.iter().find(|(keyword, _)| *keyword == value) {
+        return Ok(*factor);
+    }
+    let relative = value.starts_with(['+', '-']);
+    let factor = if let Some(percent) = value.strip_suffix('%') {
+        percent.parse::<f32>().ok().map(|percent| match relative {
+            true => 1.0 + percent / 100.0,
+            false => percent / 100.0,
+        })
+    } else if let Some(decibels) = value.strip_suffix("dB").filter(|_| attribute == "volume") {
+        decibels
+            .parse::<f32>()
+            .ok()
+            .map(|decibels| 10f32.powf(decibels / 20.0))
+    } else {
+        // e.g. a rate of `1.5`
+        value.parse::<f32>().ok().filter(|_| !relative)
+    };
+    match factor.filter(|factor| factor.is_finite()) {
+        Some(factor) if relative => Ok((current * factor).max(0.0)),
+        Some(factor) => Ok(factor.max(0.0)),
+        None => Err(invalid(format!("unsupported {} `{}`", attribute, value))),
+    }
+}
+
+/// Reads every character on its own, e.g. `ABC` as `A B C`
+fn spell_out(text: &str) -> String {
+    Vec::from_iter(
+        text.chars()
+            .filter(|c| !c.is_whitespace())
+            .map(String::from),
+    )
+    .join(" ")
+}
+
+/// Rewrites a date written in the given `say-as` format as an ISO date, e.g. `3/5/2024` in
+/// the `mdy` format as `2024-03-05`, for the voice's text normalizer to read in its language.
+/// Returns `None` unless the text holds a full date with a four-digit year.
+fn iso_date(text: &str, format: &str) -> Option<String> {
+    let parts = Vec::from_iter(
+        text.split(|c: char| !c.is_alphanumeric())
+            .filter(|part| !part.is_empty()),
+    );
+    if parts.len() != format.len() {
+        return None;
+    }
+    let (mut year, mut month, mut day) = (None, None, None);
+    for (field, part) in format.chars().zip(parts) {
+        match field {
+            'y' if part.len() == 4 && part.chars().all(|c| c.is_ascii_digit()) => year = Some(part),
+            'm' => {
+                month = Some(match part.parse::<usize>() {
+                    Ok(number @ 1..=12) => number,
+                    _ => {
+                        MONTHS.iter().position(|name| {
+                            part.len() >= 3 && name.to_lowercase().starts_with(&part.to_lowercase())
+                        })? + 1
+                    }
+                })
+            }
+            'd' => {
+                day = Some(
+                    part.parse::<u32>()
+                        .ok()
+                        .filter(|day| (1..=31).contains(day))?,
+                )
+            }
+            _ => return None,
+        }
+    }
+    Some(format!("{}-{:02}-{:02}", year?, month?, day?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(text: &str) -> SsmlPiece {
+        SsmlPiece::Text(text.to_string())
+    }
+
+    #[test]
+    fn test_parse_ssml() {
+        let segments = parse_ssml(
+            r#"<?xml version="1.0"?>
+            <speak version="1.0" xml:lang="en-US">
+                <p>
+                    <s>Call <say-as interpret-as="digits">911</say-as> now.</s>
+                    <s>Today is <say-as interpret-as="date" format="mdy">3/5/2024</say-as>.</s>
+                </p>
+                <break time="1.5s"/>
+                <prosody rate="slow" pitch="+10%">The <sub alias="World Wide Web Consortium">W3C</sub>
+                    says <phoneme alphabet="ipa" ph="təmɑːtəʊ">tomato</phoneme> &amp; more.</prosody>
+                <voice name="amy"><!-- second speaker -->Bye</voice>
+            </speak>"#,
+        )
+        .unwrap();
+        let slow = Prosody {
+            rate: 0.75,
+            pitch: 1.1,
+            volume: 1.0,
+        };
+        assert_eq!(
+            segments,
+            vec![
+                SsmlSegment::Speech {
+                    pieces: vec![text("Call 9 1 1 now.")],
+                    prosody: Prosody::default(),
+                    voice: None,
+                },
+                SsmlSegment::Speech {
+                    pieces: vec![text("Today is 2024-03-05.")],
+                    prosody: Prosody::default(),
+                    voice: None,
+                },
+                SsmlSegment::Break(Duration::from_millis(2250)),
+                SsmlSegment::Speech {
+                    pieces: vec![
+                        text("The World Wide Web Consortium\n                    says "),
+                        SsmlPiece::Phonemes("təmɑːtəʊ".to_string()),
+                        text(" & more."),
+                    ],
+                    prosody: slow,
+                    voice: None,
+                },
+                SsmlSegment::Speech {
+                    pieces: vec![text("Bye")],
+                    prosody: Prosody::default(),
+                    voice: Some("amy".to_string()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cdata_is_spoken() {
+        assert_eq!(
+            parse_ssml("<speak>Fish <![CDATA[& <chips>]]> please</speak>").unwrap(),
+            vec![SsmlSegment::Speech {
+                pieces: vec![text("Fish & <chips> please")],
+                prosody: Prosody::default(),
+                voice: None,
+            }]
+        );
+        assert!(parse_ssml("<speak><![CDATA[Fish</speak>").is_err());
+    }
+
+    #[test]
+    fn test_iso_date() {
+        assert_eq!(iso_date("3/5/2024", "mdy").as_deref(), Some("2024-03-05"));
+        assert_eq!(iso_date("5 Mar 2024", "dmy").as_deref(), Some("2024-03-05"));
+        assert_eq!(iso_date("2024-12-31", "ymd").as_deref(), Some("2024-12-31"));
+        // Partial dates and two-digit years are left for the normalizer as written
+        assert_eq!(iso_date("3/5", "md"), None);
+        assert_eq!(iso_date("3/5/24", "mdy"), None);
+        // The voice's normalizer reads the date in its own language
+        let german = crate::normalize::normalizer_for("de").unwrap();
+        let spoken = german.normalize("am 2024-03-05");
+        assert!(spoken.contains("März"), "{}", spoken);
+        assert!(!spoken.contains(char::is_numeric), "{}", spoken);
+    }
+
+    #[test]
+    fn test_invalid_ssml() {
+        assert!(parse_ssml("<speak>Hello").is_err());
+        assert!(parse_ssml("<speak>Hello</s>").is_err());
+        assert!(parse_ssml(r#"<break time="soon"/>"#).is_err());
+        assert!(parse_ssml(r#"<prosody rate="very">Hi</prosody>"#).is_err());
+        assert!(parse_ssml(r#"<phoneme alphabet="x-sampa" ph="h@">hi</phoneme>"#).is_err());
+        assert!(parse_ssml("Fish &chips").is_err());
+        // Plain text is valid SSML content
+        assert_eq!(
+            parse_ssml("Hello").unwrap(),
+            vec![SsmlSegment::Speech {
+                pieces: vec![text("Hello")],
+                prosody: Prosody::default(),
+                voice: None,
+            }]
+        );
+    }
+}
@@ -0,0 +1,361 @@
+//! Runs the tiny ONNX models of `tests/fixtures/models`, written by `generate.py` there.
+//! Each phoneme id becomes one sample holding `id * length_scale`, plus the speaker id
+//! for the streaming voice. The models embed 6 symbols.
+
+use piper_rs::speaker_embedding::SpeakerEmbedding;
+use piper_rs::{
+    from_config_bytes, from_config_bytes_with_options, from_streaming_config_bytes, AudioChunk,
+    ModelOptions, OptimizationLevel, PiperError, PiperModel, PiperModelBuilder, PiperResult,
+    PiperSynthesisConfig, Speaker, VoiceOptions,
+};
+use std::path::Path;
+use std::sync::Arc;
+
+const CONFIG: &[u8] = br#"{
+    "audio": { "sample_rate": 22050, "hop_length": 1 },
+    "num_speakers": 1,
+    "speaker_id_map": {},
+    "espeak": { "voice": "en-us" },
+    "inference": { "noise_scale": 0.667, "length_scale": 1.0, "noise_w": 0.8 },
+    "num_symbols": 6,
+    "phoneme_id_map": { "_": [0], "^": [1], "$": [2], " ": [3], "a": [4], "b": [5] }
+}"#;
+const STREAMING_CONFIG: &[u8] = br#"{
+    "audio": { "sample_rate": 22050, "hop_length": 1 },
+    "streaming": true,
+    "num_speakers": 2,
+    "speaker_id_map": { "a": 0, "b": 1 },
+    "espeak": { "voice": "en-us" },
+    "inference": { "noise_scale": 0.667, "length_scale": 1.0, "noise_w": 0.8 },
+    "num_symbols": 6,
+    "phoneme_id_map": { "_": [0], "^": [1], "$": [2], " ": [3], "a": [4], "b": [5] }
+}"#;
+const VITS: &[u8] = include_bytes!("fixtures/models/vits.onnx");
+const VITS_F16: &[u8] = include_bytes!("fixtures/models/vits_f16.onnx");
+const ENCODER_F16: &[u8] = include_bytes!("fixtures/models/encoder_f16.onnx");
+const DECODER_F16: &[u8] = include_bytes!("fixtures/models/decoder_f16.onnx");
+/// Ids of the phonemes "ab": bos, then each phoneme followed by pad, then eos
+const AB_IDS: [f32; 7] = [1.0, 0.0, 4.0, 0.0, 5.0, 0.0, 2.0];
+
+fn load(model: &[u8], batch_size: usize) -> Arc<dyn PiperModel + Send + Sync> {
+    let voice_options = VoiceOptions {
+        batch_size: Some(batch_size),
+        ..Default::default()
+    };
+    PiperModelBuilder::from_config_bytes(CONFIG)
+        .onnx_bytes(model)
+        .voice_options(voice_options)
+        .build()
+        .unwrap_or_else(|e| panic!("{}", e))
+}
+
+fn speak_batch(model: &Arc<dyn PiperModel + Send + Sync>, sentences: &[&str]) -> Vec<Vec<f32>> {
+    let sentences = Vec::from_iter(sentences.iter().map(|s| s.to_string()));
+    let batch = model
+        .speak_batch(sentences)
+        .unwrap_or_else(|e| panic!("{}", e));
+    Vec::from_iter(batch.into_iter().map(|audio| audio.into_vec()))
+}
+
+fn speak(model: &Arc<dyn PiperModel + Send + Sync>, config: &PiperSynthesisConfig) -> Vec<f32> {
+    model
+        .speak_one_sentence_with_config("ab".to_string(), config)
+        .unwrap_or_else(|e| panic!("{}", e))
+        .into_vec()
+}
+
+fn stream(
+    model: &Arc<dyn PiperModel + Send + Sync>,
+    phonemes: &str,
+    chunk_size: usize,
+    chunk_padding: usize,
+) -> Vec<Vec<f32>> {
+    let chunks = model
+        .stream_synthesis(phonemes.to_string(), chunk_size, chunk_padding)
+        .unwrap_or_else(|e| panic!("{}", e));
+    Vec::from_iter(chunks.map(|chunk| chunk.unwrap_or_else(|e| panic!("{}", e)).into_vec()))
+}
+
+/// `config` with the given replacements applied to its JSON
+fn edit_config(config: &[u8], replacements: &[(&str, &str)]) -> Vec<u8> {
+    let mut config = String::from_utf8(config.to_vec()).unwrap();
+    for (from, to) in replacements {
+        assert!(config.contains(from), "{}", from);
+        config = config.replace(from, to);
+    }
+    config.into_bytes()
+}
+
+fn mismatch(result: PiperResult<Arc<dyn PiperModel + Send + Sync>>) -> String {
+    match result {
+        Err(PiperError::ModelMismatch(message)) => message,
+        Err(e) => panic!("expected a model mismatch, got {}", e),
+        Ok(_) => panic!("expected a model mismatch, the model loaded"),
+    }
+}
+
+fn offset(samples: &[f32], value: f32) -> Vec<f32> {
+    Vec::from_iter(samples.iter().map(|sample| sample + value))
+}
+
+#[test]
+fn test_batched_output_matches_sequential() {
+    let sentences = ["ab", "a", "ba ab", "b"];
+    let sequential = speak_batch(&load(VITS, 1), &sentences);
+    assert_eq!(sequential[0], vec![1.0, 0.0, 4.0, 0.0, 5.0, 0.0, 2.0]);
+    // Trimmed with the summed phoneme durations, the only output next to the audio
+    for batch_size in [2, 3, 4] {
+        assert_eq!(speak_batch(&load(VITS, batch_size), &sentences), sequential);
+    }
+}
+
+#[test]
+fn test_f16_model() {
+    let model = load(VITS_F16, 1);
+    let config = model.get_fallback_synthesis_config().unwrap();
+    assert_eq!(speak(&model, &config), AB_IDS);
+    // The scales input is converted to f16
+    let slow = PiperSynthesisConfig {
+        length_scale: 2.0,
+        ..config
+    };
+    assert_eq!(
+        speak(&model, &slow),
+        Vec::from_iter(AB_IDS.map(|id| id * 2.0))
+    );
+    // So are the durations read back for the alignment
+    let audio = model.speak_one_sentence("ab".to_string()).unwrap();
+    let alignment = audio.alignment.expect("the model exports durations");
+    assert_eq!(alignment.len(), AB_IDS.len());
+    assert_eq!(stream(&model, "ab", 44, 3).concat(), AB_IDS);
+}
+
+#[test]
+fn test_f16_streaming_model() {
+    let model = from_streaming_config_bytes(STREAMING_CONFIG, ENCODER_F16, DECODER_F16)
+        .unwrap_or_else(|e| panic!("{}", e));
+    let config = model.get_fallback_synthesis_config().unwrap();
+    assert_eq!(speak(&model, &config), AB_IDS);
+    let speaker_b = PiperSynthesisConfig {
+        speaker: Some(Speaker::Name("b".to_string())),
+        ..config.clone()
+    };
+    assert_eq!(speak(&model, &speaker_b), offset(&AB_IDS, 1.0));
+
+    // `g` is read from the f16 encoder, kept as f32, and converted back for the decoder
+    let embedding = model
+        .speaker_embedding(&Speaker::Name("b".to_string()))
+        .unwrap();
+    assert_eq!(embedding.values, vec![1.0]);
+    let custom = PiperSynthesisConfig {
+        speaker: Some(Speaker::from(SpeakerEmbedding::new(vec![3.0]))),
+        ..config
+    };
+    assert_eq!(speak(&model, &custom), offset(&AB_IDS, 3.0));
+
+    // Short sentences are decoded at once, long ones chunk by chunk
+    assert_eq!(stream(&model, "ab", 44, 3), vec![AB_IDS.to_vec()]);
+    let chunks = stream(&model, &"ab".repeat(40), 8, 2);
+    assert!(chunks.len() > 1);
+    assert_eq!(chunks.iter().map(Vec::len).sum::<usize>(), 4 * 40 + 3);
+}
+
+#[test]
+fn test_model_mismatches() {
+    // The embedding table is smaller than the config says
+    let seven_symbols = edit_config(
+        CONFIG,
+        &[
+            (r#""num_symbols": 6"#, r#""num_symbols": 7"#),
+            (r#""b": [5]"#, r#""b": [5], "c": [6]"#),
+        ],
+    );
+    let message = mismatch(from_config_bytes(&seven_symbols, VITS));
+    assert!(message.contains("phoneme id 6"), "{}", message);
+
+    let two_speakers = edit_config(
+        CONFIG,
+        &[
+            (r#""num_speakers": 1"#, r#""num_speakers": 2"#),
+            (
+                r#""speaker_id_map": {}"#,
+                r#""speaker_id_map": { "a": 0, "b": 1 }"#,
+            ),
+        ],
+    );
+    let message = mismatch(from_config_bytes(&two_speakers, VITS));
+    assert!(message.contains("has no `sid` input"), "{}", message);
+
+    let message = mismatch(from_streaming_config_bytes(
+        STREAMING_CONFIG,
+        DECODER_F16,
+        ENCODER_F16,
+    ));
+    assert!(message.contains("has no `input` input"), "{}", message);
+
+    // A single-speaker config for an encoder taking a speaker id only logs a warning
+    let single_speaker = edit_config(
+        STREAMING_CONFIG,
+        &[
+            (r#""num_speakers": 2"#, r#""num_speakers": 1"#),
+            (
+                r#""speaker_id_map": { "a": 0, "b": 1 }"#,
+                r#""speaker_id_map": {}"#,
+            ),
+        ],
+    );
+    let model = from_streaming_config_bytes(&single_speaker, ENCODER_F16, DECODER_F16)
+        .unwrap_or_else(|e| panic!("{}", e));
+    let config = model.get_fallback_synthesis_config().unwrap();
+    assert_eq!(speak(&model, &config), AB_IDS);
+}
+
+#[test]
+fn test_builder_with_explicit_paths() {
+    let models = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/models");
+    let dir = std::env::temp_dir().join(format!("piper-rs-fixtures-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let config_path = dir.join("voice.onnx.json");
+    let streaming_config_path = dir.join("streaming.onnx.json");
+    std::fs::write(&config_path, CONFIG).unwrap();
+    std::fs::write(&streaming_config_path, STREAMING_CONFIG).unwrap();
+
+    let model = PiperModelBuilder::new(&config_path)
+        .onnx_path(models.join("vits.onnx"))
+        .build()
+        .unwrap_or_else(|e| panic!("{}", e));
+    let config = model.get_fallback_synthesis_config().unwrap();
+    assert_eq!(speak(&model, &config), AB_IDS);
+    let model = PiperModelBuilder::new(&streaming_config_path)
+        .encoder_path(models.join("encoder_f16.onnx"))
+        .decoder_path(models.join("decoder_f16.onnx"))
+        .build()
+        .unwrap_or_else(|e| panic!("{}", e));
+    assert!(model.supports_streaming_output());
+    assert_eq!(speak(&model, &config), AB_IDS);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_configs_leave_the_fallback_unchanged() {
+    for model in [
+        load(VITS, 1),
+        load(VITS, 2),
+        from_streaming_config_bytes(STREAMING_CONFIG, ENCODER_F16, DECODER_F16)
+            .unwrap_or_else(|e| panic!("{}", e)),
+    ] {
+        let fallback = model.get_fallback_synthesis_config().unwrap();
+        let overridden = PiperSynthesisConfig {
+            noise_scale: 0.1,
+            length_scale: 2.0,
+            noise_w: 0.2,
+            ..fallback.clone()
+        };
+        let slow = Vec::from_iter(AB_IDS.map(|id| id * 2.0));
+        assert_eq!(speak(&model, &overridden), slow);
+        let batch = model
+            .speak_batch_with_config(vec!["ab".to_string(), "ab".to_string()], &overridden)
+            .unwrap_or_else(|e| panic!("{}", e));
+        assert!(batch.into_iter().all(|audio| audio.into_vec() == slow));
+
+        let unchanged = model.get_fallback_synthesis_config().unwrap();
+        assert_eq!(unchanged.noise_scale, fallback.noise_scale);
+        assert_eq!(unchanged.length_scale, fallback.length_scale);
+        assert_eq!(unchanged.noise_w, fallback.noise_w);
+        assert!(unchanged.speaker.is_none());
+        assert_eq!(speak_batch(&model, &["ab"]), vec![AB_IDS.to_vec()]);
+    }
+}
+
+/// Start and end of each phoneme, in samples of the fixture voices
+fn alignment_samples(chunk: &AudioChunk) -> Vec<(f32, f32)> {
+    let to_samples = |ms: f32| (ms * 22050.0 / 1000.0 * 100.0).round() / 100.0;
+    let alignment = chunk
+        .alignment
+        .as_ref()
+        .expect("the chunk has an alignment");
+    Vec::from_iter(
+        alignment
+            .iter()
+            .map(|phoneme| (to_samples(phoneme.start_ms), to_samples(phoneme.end_ms))),
+    )
+}
+
+#[test]
+fn test_alignment_offsets() {
+    // One frame per phoneme id and one sample per frame
+    let model = load(VITS, 1);
+    let audio = model.speak_one_sentence("ab".to_string()).unwrap();
+    let alignment = audio.alignment.as_ref().unwrap();
+    let end_ms = alignment.last().unwrap().end_ms;
+    assert_eq!(
+        (end_ms * 22050.0 / 1000.0).round() as usize,
+        audio.samples.len()
+    );
+
+    // Non-streaming voices stream clause by clause. The 3 last samples of the first clause
+    // are held back and crossfaded with the start of the second one.
+    let chunks = Vec::from_iter(
+        model
+            .stream_synthesis("ab, ba".to_string(), 44, 3)
+            .unwrap()
+            .map(|chunk| chunk.unwrap_or_else(|e| panic!("{}", e))),
+    );
+    let lengths = Vec::from_iter(chunks.iter().map(|chunk| chunk.samples.len()));
+    assert_eq!(lengths, vec![4, 7]);
+    let first = alignment_samples(&chunks[0]);
+    let second = alignment_samples(&chunks[1]);
+    assert_eq!(first.first().unwrap().0, 0.0);
+    assert_eq!(first.last().unwrap().1, 7.0);
+    assert_eq!(second.first().unwrap().0, 4.0);
+    assert_eq!(second.last().unwrap().1, 11.0);
+
+    // Streaming voices align the whole sentence with its first chunk
+    let model = from_streaming_config_bytes(STREAMING_CONFIG, ENCODER_F16, DECODER_F16)
+        .unwrap_or_else(|e| panic!("{}", e));
+    let phonemes = "ab".repeat(40);
+    let chunks = Vec::from_iter(
+        model
+            .stream_synthesis(phonemes, 8, 2)
+            .unwrap()
+            .map(|chunk| chunk.unwrap_or_else(|e| panic!("{}", e))),
+    );
+    assert!(chunks.len() > 1);
+    let num_samples = chunks
+        .iter()
+        .map(|chunk| chunk.samples.len())
+        .sum::<usize>();
+    assert_eq!(
+        alignment_samples(&chunks[0]).last().unwrap().1,
+        num_samples as f32
+    );
+    assert!(chunks[1..].iter().all(|chunk| chunk.alignment.is_none()));
+}
+
+#[test]
+fn test_options_reach_the_session() {
+    let dir = std::env::temp_dir().join(format!("piper-rs-options-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let options = ModelOptions {
+        intra_threads: Some(1),
+        optimization_level: Some(OptimizationLevel::Basic),
+        optimized_model_path: Some(dir.join("optimized.onnx")),
+        ..Default::default()
+    };
+    let model =
+        from_config_bytes_with_options(CONFIG, VITS, &options).unwrap_or_else(|e| panic!("{}", e));
+    assert_eq!(speak_batch(&model, &["ab"]), vec![AB_IDS.to_vec()]);
+    assert!(dir.join("optimized.onnx").is_file());
+
+    PiperModelBuilder::from_config_bytes(STREAMING_CONFIG)
+        .encoder_bytes(ENCODER_F16)
+        .decoder_bytes(DECODER_F16)
+        .options(options)
+        .build()
+        .unwrap_or_else(|e| panic!("{}", e));
+    assert!(dir.join("encoder.optimized.onnx").is_file());
+    assert!(dir.join("decoder.optimized.onnx").is_file());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,191 @@
+#!/usr/bin/env python3
+"""Writes the tiny ONNX models used by `tests/fixture_models.rs`.
+
+The graphs have the inputs and outputs of piper VITS exports, but no weights:
+each phoneme id becomes one mel frame and one sample whose value is
+`id * length_scale`, plus the speaker id for the streaming voice, so tests can
+predict the audio exactly. Like real voices, ids are looked up in an embedding
+table of `NUM_SYMBOLS` rows, and larger ids fail to run. The `_f16` models take and return half precision
+floats, like voices converted with onnxconverter-common's `keep_io_types=False`.
+The protobuf is encoded by hand so that the script has no dependencies.
+
+Run it from any directory to regenerate the `.onnx` files next to it.
+"""
+
+import struct
+from pathlib import Path
+
+FLOAT, INT64, FLOAT16 = 1, 7, 10
+# Rows of the phoneme embedding table, row `id` holds `id`
+NUM_SYMBOLS = 6
+OPSET = 13
+IR_VERSION = 8
+
+
+def varint(value):
+    value &= (1 << 64) - 1
+    out = bytearray()
+    while True:
+        byte = value & 0x7F
+        value >>= 7
+        if value:
+            out.append(byte | 0x80)
+        else:
+            out.append(byte)
+            return bytes(out)
+
+
+def field_int(number, value):
+    return varint(number << 3) + varint(value)
+
+
+def field_bytes(number, value):
+    if isinstance(value, str):
+        value = value.encode()
+    return varint(number << 3 | 2) + varint(len(value)) + value
+
+
+def tensor(name, dtype, dims, values):
+    packed = struct.pack(
+        "<%d%s" % (len(values), "q" if dtype == INT64 else "f"), *values
+    )
+    return (
+        b"".join(field_int(1, dim) for dim in dims)
+        + field_int(2, dtype)
+        + field_bytes(8, name)
+        + field_bytes(9, packed)
+    )
+
+
+def value_info(name, dtype, dims):
+    shape = b"".join(
+        field_bytes(1, field_int(1, dim) if isinstance(dim, int) else field_bytes(2, dim))
+        for dim in dims
+    )
+    tensor_type = field_int(1, dtype) + field_bytes(2, shape)
+    return field_bytes(1, name) + field_bytes(2, field_bytes(1, tensor_type))
+
+
+class Graph:
+    def __init__(self, name):
+        self.name = name
+        self.nodes = []
+        self.inputs = []
+        self.outputs = []
+        self.initializers = [
+            tensor("zero", INT64, [], [0]),
+            tensor("one", INT64, [], [1]),
+            tensor("axis_1", INT64, [1], [1]),
+            tensor("axes_1_2", INT64, [2], [1, 2]),
+        ]
+        self.count = 0
+
+    def node(self, op_type, *inputs, output=None, to=None):
+        self.count += 1
+        output = output or "%s_%d" % (op_type.lower(), self.count)
+        body = b"".join(field_bytes(1, name) for name in inputs)
+        body += field_bytes(2, output) + field_bytes(4, op_type)
+        if to is not None:
+            # AttributeProto of type INT
+            body += field_bytes(5, field_bytes(1, "to") + field_int(3, to) + field_int(20, 2))
+        self.nodes.append(body)
+        return output
+
+    def input(self, name, dtype, dims):
+        self.inputs.append(value_info(name, dtype, dims))
+        return name
+
+    def float_input(self, name, dtype, dims):
+        """Declares a float input and returns it as f32"""
+        self.input(name, dtype, dims)
+        return self.node("Cast", name, to=FLOAT) if dtype == FLOAT16 else name
+
+    def output(self, name, value, dtype, dims):
+        """Declares an output, converting the f32 `value` to `dtype`"""
+        if dtype == FLOAT16:
+            self.node("Cast", value, output=name, to=FLOAT16)
+        else:
+            self.node("Identity", value, output=name)
+        self.outputs.append(value_info(name, dtype, dims))
+
+    def serialize(self):
+        graph = b"".join(field_bytes(1, node) for node in self.nodes)
+        graph += field_bytes(2, self.name)
+        graph += b"".join(field_bytes(5, init) for init in self.initializers)
+        graph += b"".join(field_bytes(11, info) for info in self.inputs)
+        graph += b"".join(field_bytes(12, info) for info in self.outputs)
+        opset = field_bytes(1, "") + field_int(2, OPSET)
+        return (
+            field_int(1, IR_VERSION)
+            + field_bytes(2, "piper-rs fixtures")
+            + field_bytes(7, graph)
+            + field_bytes(8, opset)
+        )
+
+
+def text_inputs(graph, float_type):
+    """Returns the embedded phoneme ids and the length scale as f32 and a `[batch, 1, phonemes]` mask"""
+    graph.initializers.append(tensor("symbols", FLOAT, [NUM_SYMBOLS], range(NUM_SYMBOLS)))
+    ids = graph.input("input", INT64, ["batch", "phonemes"])
+    lengths = graph.input("input_lengths", INT64, ["batch"])
+    scales = graph.float_input("scales", float_type, [3])
+    length_scale = graph.node("Gather", scales, "one")
+    num_phonemes = graph.node("Gather", graph.node("Shape", ids), "one")
+    positions = graph.node("Range", "zero", num_phonemes, "one")
+    valid = graph.node("Less", positions, graph.node("Unsqueeze", lengths, "axis_1"))
+    mask = graph.node("Unsqueeze", graph.node("Cast", valid, to=FLOAT), "axis_1")
+    ids = graph.node("Unsqueeze", graph.node("Gather", "symbols", ids), "axis_1")
+    return ids, length_scale, mask
+
+
+def speaker_inputs(graph):
+    """Returns the speaker id as a `[batch, 1, 1]` f32 conditioning vector"""
+    sid = graph.input("sid", INT64, ["batch"])
+    return graph.node("Unsqueeze", graph.node("Cast", sid, to=FLOAT), "axes_1_2")
+
+
+def vits(float_type):
+    """Single-speaker voice that only exports its audio and the phoneme durations"""
+    graph = Graph("vits")
+    ids, length_scale, mask = text_inputs(graph, float_type)
+    audio = graph.node("Mul", graph.node("Mul", ids, length_scale), mask)
+    graph.output("output", audio, float_type, ["batch", 1, "samples"])
+    graph.output("durations", mask, float_type, ["batch", 1, "phonemes"])
+    return graph
+
+
+def encoder(float_type):
+    """Encoder of a two-speaker streaming voice, with one mel frame per phoneme id"""
+    graph = Graph("encoder")
+    ids, length_scale, mask = text_inputs(graph, float_type)
+    g = speaker_inputs(graph)
+    z = graph.node("Mul", graph.node("Mul", ids, length_scale), mask)
+    graph.output("z", z, float_type, ["batch", 1, "frames"])
+    graph.output("y_mask", mask, float_type, ["batch", 1, "frames"])
+    graph.output("g", g, float_type, ["batch", 1, 1])
+    return graph
+
+
+def decoder(float_type):
+    """Decoder adding the speaker's conditioning vector to each frame, one sample per frame"""
+    graph = Graph("decoder")
+    z = graph.float_input("z", float_type, ["batch", 1, "frames"])
+    y_mask = graph.float_input("y_mask", float_type, ["batch", 1, "frames"])
+    g = graph.float_input("g", float_type, ["batch", 1, 1])
+    audio = graph.node("Mul", graph.node("Add", z, g), y_mask)
+    graph.output("output", audio, float_type, ["batch", 1, "samples"])
+    return graph
+
+
+MODELS = {
+    "vits.onnx": lambda: vits(FLOAT),
+    "vits_f16.onnx": lambda: vits(FLOAT16),
+    "encoder_f16.onnx": lambda: encoder(FLOAT16),
+    "decoder_f16.onnx": lambda: decoder(FLOAT16),
+}
+
+
+if __name__ == "__main__":
+    directory = Path(__file__).parent
+    for file_name, build in MODELS.items():
+        (directory / file_name).write_bytes(build().serialize())
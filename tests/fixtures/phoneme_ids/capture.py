@@ -0,0 +1,53 @@
+#!/usr/bin/env python3
+"""Captures `en_us.json` from upstream piper-phonemize.
+
+Needs the `piper-phonemize==1.1.0` wheel, which bundles espeak-ng and the
+default phoneme id map that the en_US piper voices ship:
+
+    pip install piper-phonemize==1.1.0
+    python3 tests/fixtures/phoneme_ids/capture.py
+
+Phonemes come from `phonemize_espeak(text, "en-us")` and ids from
+`phoneme_ids_espeak`. Upstream drops phonemes that have no id without
+reporting them, so `missing` counts the phonemes absent from the map.
+"""
+
+import json
+from collections import Counter
+from importlib.metadata import version
+from pathlib import Path
+
+from piper_phonemize import get_espeak_map, phoneme_ids_espeak, phonemize_espeak
+
+TEXTS = [
+    "This is a test.",
+    "Hello world!",
+    "The quick brown fox jumps over the lazy dog.",
+    "Wait, what?",
+    "Thirty-three thin thieves.",
+]
+
+
+def sentence(text, phoneme_id_map):
+    # Each text is a single sentence
+    (phonemes,) = phonemize_espeak(text, "en-us")
+    missing = Counter(p for p in phonemes if p not in phoneme_id_map)
+    return {
+        "text": text,
+        "phonemes": "".join(phonemes),
+        "ids": phoneme_ids_espeak(phonemes),
+        "missing": dict(sorted(missing.items())),
+    }
+
+
+if __name__ == "__main__":
+    phoneme_id_map = get_espeak_map()
+    fixture = {
+        "source": "Captured with piper-phonemize %s by capture.py: phonemize_espeak(text, \"en-us\") and phoneme_ids_espeak."
+        % version("piper-phonemize"),
+        "phoneme_id_map": phoneme_id_map,
+        "phoneme_map": {},
+        "sentences": [sentence(text, phoneme_id_map) for text in TEXTS],
+    }
+    path = Path(__file__).parent / "en_us.json"
+    path.write_text(json.dumps(fixture, ensure_ascii=False, indent=1) + "\n")